@@ -1,10 +1,8 @@
 use source_span::{
 	fmt::{Color, Formatter, Style},
-	Position, Span,
+	Encoding, Position, Span,
 };
 use std::fs::File;
-use std::io::Read;
-use utf8_decode::UnsafeDecoder;
 
 #[derive(Clone, Default)]
 pub struct Token {
@@ -22,9 +20,8 @@ pub enum Kind {
 
 fn main() -> std::io::Result<()> {
 	let file = File::open("examples/fib.txt")?;
-	let chars = UnsafeDecoder::new(file.bytes());
 	let metrics = source_span::DEFAULT_METRICS;
-	let buffer = source_span::SourceBuffer::new(chars, Position::default(), metrics);
+	let buffer = source_span::SourceBuffer::from_reader(file, Encoding::Utf8, Position::default(), metrics);
 
 	let mut fmt = Formatter::with_margin_color(Color::Blue);
 
@@ -3,21 +3,7 @@ use source_span::{
 	Position, Span,
 };
 
-#[derive(Clone, Default)]
-pub struct Token {
-	string: String,
-	span: Span,
-}
-
-#[derive(PartialEq)]
-pub enum Kind {
-	Space,
-	Separator,
-	Alphabetic,
-	Numeric,
-}
-
-const SOURCE: &'static str = "################################
+const SOURCE: &str = "################################
 ################################
 ################################
 ################################
@@ -1,71 +1,36 @@
-use source_span::Span;
+use source_span::{Encoding, Position, Scanner, SourceBuffer};
 use std::fs::File;
-use std::io::Read;
-use utf8_decode::UnsafeDecoder;
-
-#[derive(Clone, Default)]
-pub struct Token {
-    string: String,
-    span: Span,
-}
-
-#[derive(PartialEq)]
-pub enum Kind {
-    Space,
-    Separator,
-    Alphabetic,
-    Numeric,
-}
 
 fn main() -> std::io::Result<()> {
     let file = File::open("examples/file.txt")?;
-    let chars = UnsafeDecoder::new(file.bytes());
 	let metrics = source_span::DEFAULT_METRICS;
+    let buffer = SourceBuffer::from_reader(file, Encoding::Utf8, Position::default(), metrics);
+    let mut scanner = Scanner::new(buffer);
 
     let mut tokens = Vec::new();
-    let mut current = Token::default();
-    let mut kind = Kind::Space;
 
-    for c in chars {
-        let c = c?; // report eventual errors.
-
-        let new_kind;
+    while let Some(c) = scanner.peek(0)? { // report eventual errors.
         if c.is_whitespace() {
-            new_kind = Kind::Space;
-        } else if c.is_alphabetic() {
-            new_kind = Kind::Alphabetic;
-        } else if c.is_numeric() {
-            new_kind = Kind::Numeric;
-        } else {
-            new_kind = Kind::Separator;
+            scanner.eat_while(char::is_whitespace)?;
+            scanner.skip_token();
+            continue
         }
 
-        if kind != new_kind || kind == Kind::Separator {
-            // save the current token.
-            if kind != Kind::Space {
-                tokens.push(current.clone());
-            }
-
-            // reset current token.
-            current.string.clear();
-            current.span.clear(); // the span here is moved to the end of
-                                  // itself.
-        }
-
-        if new_kind != Kind::Space {
-            current.string.push(c);
+        if c.is_alphabetic() {
+            scanner.eat_while(char::is_alphabetic)?;
+        } else if c.is_numeric() {
+            scanner.eat_while(char::is_numeric)?;
+        } else {
+            // separator.
+            scanner.bump()?;
         }
 
-        current.span.push(c, &metrics);
-        kind = new_kind;
-    }
-
-    if !current.string.is_empty() {
-        tokens.push(current);
+        // save the current token, and start the next one.
+        tokens.push(scanner.take_token()?);
     }
 
     for token in &tokens {
-        println!("{}: {}", token.string, token.span);
+        println!("{}: {}", token.as_str(), token.span());
     }
 
     Ok(())
@@ -0,0 +1,5 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use source_span_fuzz::{check_layout, LayoutInput};
+
+fuzz_target!(|input: LayoutInput| check_layout(&input));
@@ -0,0 +1,5 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use source_span_fuzz::{check_render, RenderInput};
+
+fuzz_target!(|input: RenderInput| check_render(&input));
@@ -0,0 +1,369 @@
+//! Fuzzing inputs and invariants of the `source-span` crate.
+//!
+//! The fuzz targets are run with [`cargo fuzz`](https://github.com/rust-fuzz/cargo-fuzz)
+//! from this directory:
+//!
+//! ```text
+//! cargo +nightly fuzz run render
+//! cargo +nightly fuzz run layout
+//! ```
+use arbitrary::Arbitrary;
+use source_span::{
+	fmt::{Formatter, Mode, Style, TabStyle},
+	DefaultMetrics, Layout, LineBreaks, Metrics, Position, SourceBuffer, Span, VisibleMetrics,
+};
+
+/// Character of a generated text.
+///
+/// Line breaks, tabulations and invisible characters are over-represented.
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub enum TextChar {
+	Letter,
+	Space,
+	Newline,
+	CarriageReturn,
+	Tab,
+	Null,
+	Delete,
+	ZeroWidthSpace,
+	LineSeparator,
+	Wide,
+	Other(char),
+}
+
+impl TextChar {
+	fn char(self) -> char {
+		match self {
+			TextChar::Letter => 'a',
+			TextChar::Space => ' ',
+			TextChar::Newline => '\n',
+			TextChar::CarriageReturn => '\r',
+			TextChar::Tab => '\t',
+			TextChar::Null => '\0',
+			TextChar::Delete => '\u{7f}',
+			TextChar::ZeroWidthSpace => '\u{200b}',
+			TextChar::LineSeparator => '\u{2028}',
+			TextChar::Wide => '字',
+			TextChar::Other(c) => c,
+		}
+	}
+}
+
+/// Generated text.
+#[derive(Arbitrary, Debug)]
+pub struct Text(Vec<TextChar>);
+
+impl Text {
+	fn string(&self) -> String {
+		self.0.iter().map(|c| c.char()).collect()
+	}
+}
+
+/// Line or column index, favoring small values and the bounds of `usize`.
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub enum Index {
+	Small(u8),
+	BeforeMax,
+	Max,
+}
+
+impl Index {
+	fn get(self) -> usize {
+		match self {
+			Index::Small(i) => i as usize,
+			Index::BeforeMax => usize::MAX - 1,
+			Index::Max => usize::MAX,
+		}
+	}
+}
+
+/// Generated position.
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub struct GenPosition {
+	line: Index,
+	column: Index,
+}
+
+impl GenPosition {
+	fn position(self) -> Position {
+		Position::new(self.line.get(), self.column.get())
+	}
+}
+
+/// Generated span.
+///
+/// The positions are sorted to build the span. Invalid spans are ignored.
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub struct GenSpan(GenPosition, GenPosition, GenPosition);
+
+impl GenSpan {
+	fn span(self) -> Option<Span> {
+		let mut p = [self.0.position(), self.1.position(), self.2.position()];
+		p.sort();
+		Span::try_new(p[0], p[1], p[2]).ok()
+	}
+}
+
+/// Generated metrics.
+#[derive(Arbitrary, Debug)]
+pub struct GenMetrics {
+	tab_stop: u8,
+	unicode_line_breaks: bool,
+	visible: bool,
+}
+
+impl GenMetrics {
+	fn metrics(&self) -> DefaultMetrics {
+		let mut metrics = DefaultMetrics::with_line_breaks(if self.unicode_line_breaks {
+			LineBreaks::Unicode
+		} else {
+			LineBreaks::Unix
+		});
+
+		metrics.set_tab_stop(1 + (self.tab_stop % 16) as usize);
+		metrics
+	}
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub enum GenStyle {
+	Error,
+	Warning,
+	Note,
+	Help,
+}
+
+impl GenStyle {
+	fn style(self) -> Style {
+		match self {
+			GenStyle::Error => Style::Error,
+			GenStyle::Warning => Style::Warning,
+			GenStyle::Note => Style::Note,
+			GenStyle::Help => Style::Help,
+		}
+	}
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub enum GenMode {
+	Full,
+	Short,
+	Compact,
+}
+
+impl GenMode {
+	fn mode(self) -> Mode {
+		match self {
+			GenMode::Full => Mode::Full,
+			GenMode::Short => Mode::Short,
+			GenMode::Compact => Mode::Compact,
+		}
+	}
+}
+
+/// Generated highlight.
+#[derive(Arbitrary, Debug)]
+pub struct GenHighlight {
+	span: GenSpan,
+	label: Option<String>,
+	style: GenStyle,
+}
+
+/// Input of the `render` target.
+#[derive(Arbitrary, Debug)]
+pub struct RenderInput {
+	text: Text,
+	metrics: GenMetrics,
+	span: Option<GenSpan>,
+	highlights: Vec<GenHighlight>,
+	mode: GenMode,
+	viewbox: Option<Index>,
+	line_numbers: bool,
+	arrow_tabs: bool,
+	invisible: bool,
+}
+
+impl RenderInput {
+	fn formatter<'a, I: IntoIterator<Item = &'a GenHighlight>>(&self, highlights: I) -> Formatter {
+		let mut fmt = Formatter::new();
+		fmt.set_viewbox(self.viewbox.map(Index::get));
+		fmt.set_tab_style(if self.arrow_tabs { TabStyle::Arrow } else { TabStyle::Spaces });
+		fmt.set_invisible_characters_visible(self.invisible);
+		for h in highlights {
+			if let Some(span) = h.span.span() {
+				fmt.add(span, h.label.clone(), h.style.style())
+			}
+		}
+
+		fmt
+	}
+}
+
+/// Renders the input text and checks that:
+///
+///  * the formatter never panics,
+///  * every column of a single line highlight is marked in the output.
+pub fn check_render(input: &RenderInput) {
+	let metrics = input.metrics.metrics();
+	if input.metrics.visible {
+		check_render_with(input, VisibleMetrics::new(metrics))
+	} else {
+		check_render_with(input, metrics)
+	}
+}
+
+fn check_render_with<M: Metrics + Copy>(input: &RenderInput, metrics: M) {
+	let text = input.text.string();
+
+	let span = input
+		.span
+		.and_then(GenSpan::span)
+		.unwrap_or_else(|| Span::of_string(&text, &metrics));
+	let mut fmt = input.formatter(&input.highlights);
+	fmt.set_mode(input.mode.mode());
+	fmt.set_line_numbers_visible(input.line_numbers);
+	let _ = fmt.margin_len(&span);
+	let _ = fmt
+		.render(text.chars().map(Ok::<char, ()>), span, &metrics)
+		.unwrap()
+		.to_string();
+
+	check_highlighted_columns(input, &text, metrics)
+}
+
+/// Checks that every column of the single line highlights located in the
+/// text is marked in the full rendering of the text.
+fn check_highlighted_columns<M: Metrics + Copy>(input: &RenderInput, text: &str, metrics: M) {
+	let layout = Layout::from(text.chars(), metrics);
+	let is_char = |pos: Position| layout.byte_index(text, pos).is_some();
+	let highlights: Vec<&GenHighlight> = input
+		.highlights
+		.iter()
+		.filter(|h| {
+			h.span.span().map(|span| {
+				span.start().line == span.last().line
+					&& if span.is_empty() {
+						is_char(span.start()) || span.start() == layout.span().end()
+					} else {
+						is_char(span.start()) && is_char(span.last()) && span.last() <= layout.span().last()
+					}
+			})
+			.unwrap_or(false)
+		})
+		.collect();
+
+	let span = layout.span();
+	let fmt = input.formatter(highlights.iter().copied());
+	let margin_len = fmt.margin_len(&span);
+	let output = fmt
+		.render(text.chars().map(Ok::<char, ()>), span, &metrics)
+		.unwrap()
+		.to_plain_string();
+	let rows: Vec<Vec<char>> = output.split('\n').map(|row| row.chars().collect()).collect();
+
+	// line number (or viewbox dots) of the given row, if any.
+	let margin = |row: &[char]| -> Option<String> {
+		if row.get(margin_len - 2) == Some(&'|') {
+			let prefix: String = row[..(margin_len - 2)].iter().collect();
+			Some(prefix.trim().to_string()).filter(|prefix| !prefix.is_empty())
+		} else {
+			None
+		}
+	};
+
+	for h in highlights {
+		let span = h.span.span().unwrap();
+		let line_number = (span.start().line + 1).to_string();
+		let y = rows
+			.iter()
+			.position(|row| margin(row).as_ref() == Some(&line_number))
+			.expect("highlighted line is rendered");
+
+		// rows drawn below the line.
+		let below: Vec<&Vec<char>> = rows[(y + 1)..].iter().take_while(|row| margin(row).is_none()).collect();
+
+		for column in span.start().column..=span.last().column {
+			assert!(
+				below.iter().any(|row| row.get(margin_len + column).map(|c| *c != ' ').unwrap_or(false)),
+				"column {} of {:?} is not marked:\n{}",
+				column,
+				span,
+				output
+			)
+		}
+	}
+}
+
+/// Input of the `layout` target.
+#[derive(Arbitrary, Debug)]
+pub struct LayoutInput {
+	text: Text,
+	metrics: GenMetrics,
+	ranges: Vec<(u8, u8)>,
+}
+
+/// Checks that [`Layout`] and [`SourceBuffer`] agree on the positions of the
+/// characters of the input text, and on the spans between them.
+pub fn check_layout(input: &LayoutInput) {
+	let metrics = input.metrics.metrics();
+	if input.metrics.visible {
+		check_layout_with(input, VisibleMetrics::new(metrics))
+	} else {
+		check_layout_with(input, metrics)
+	}
+}
+
+fn check_layout_with<M: Metrics + Copy>(input: &LayoutInput, metrics: M) {
+	let text = input.text.string();
+	let layout = Layout::from(text.chars(), metrics);
+	let buffer = SourceBuffer::new(text.chars().map(Ok::<char, ()>), Position::default(), metrics);
+	let line_breaks = metrics.line_breaks();
+
+	// positions of every character (excluding line break continuations) and
+	// of the end of the text.
+	let mut positions = Vec::new();
+	let mut previous = None;
+	for (i, c) in text.char_indices() {
+		if !previous.map(|p| line_breaks.continues(p, c)).unwrap_or(false) {
+			let pos = layout.position(&text, i).expect("character position");
+
+			// the first character at this position (previous characters may be
+			// 0 columns wide).
+			let first = layout.byte_index(&text, pos).expect("byte index");
+			assert!(first <= i);
+			assert_eq!(layout.position(&text, first), Some(pos));
+			assert_eq!(buffer.index_at(pos), Ok(Some(first)));
+			assert_eq!(buffer.at(pos), Ok(text[first..].chars().next()));
+
+			positions.push(pos)
+		}
+
+		previous = Some(c)
+	}
+
+	let end = layout.span().end();
+	positions.push(end);
+	assert!(positions.windows(2).all(|w| w[0] <= w[1]));
+	assert_eq!(layout.position(&text, text.len()), Some(end));
+	assert_eq!(Span::of_string(&text, &metrics), layout.span());
+	assert_eq!(buffer.iter().into_string(), Ok(text.clone()));
+	assert_eq!(buffer.span(), layout.span());
+	assert_eq!(buffer.lines(..).map(|lines| lines.iter().map(|line| line.as_str()).collect::<String>()), Ok(text.clone()));
+
+	for (a, b) in &input.ranges {
+		let start = positions[*a as usize % positions.len()];
+		let end = positions[*b as usize % positions.len()];
+
+		let span = layout.try_span_between(&text, start, end);
+		assert_eq!(buffer.try_span_between(start, end), Ok(span));
+
+		if let Ok(span) = span {
+			let slice = layout.span_slice(&text, span);
+			assert_eq!(layout.try_span_slice(&text, span), Ok(slice));
+			assert_eq!(buffer.iter_span(span).into_string().as_deref(), Ok(slice));
+			assert_eq!(buffer.slice(span).as_deref().ok(), Some(slice));
+			assert_eq!(buffer.slice_to_string(span).as_deref(), Ok(slice));
+			assert_eq!(buffer.iter_span(span).rev().collect::<Result<String, _>>(), Ok(slice.chars().rev().collect()));
+		}
+	}
+}
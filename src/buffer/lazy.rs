@@ -1,6 +1,10 @@
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
+use std::ops::{Bound, Range, RangeBounds};
+use std::io::Read;
+use std::iter::Peekable;
 
-use crate::{Metrics, Position, Span};
+use super::{BufferError, Line, SourceError};
+use crate::{Decoder, DecodeError, Encoding, Metrics, Position, Span, SpanError};
 
 /// Lazy string buffer that fills up on demand, can be iterated and indexed by
 /// character position.
@@ -8,6 +12,10 @@ use crate::{Metrics, Position, Span};
 /// The `SourceBuffer` wraps aroung a `char` iterator. It can be itself used as
 /// a `char` iterator, or as a `SourceBuffer` to access an arbitrary fragment of
 /// the input source stream.
+///
+/// By default every character read from the input stream is kept in the
+/// buffer. When parsing large streams, the lines that are not needed anymore
+/// can be discarded using [`forget_before`](SourceBuffer::forget_before).
 pub struct SourceBuffer<E, I: Iterator<Item = Result<char, E>>, M: Metrics> {
 	p: RefCell<Inner<E, I>>,
 
@@ -15,26 +23,96 @@ pub struct SourceBuffer<E, I: Iterator<Item = Result<char, E>>, M: Metrics> {
 	metrics: M,
 }
 
-struct Inner<E, I: Iterator<Item = Result<char, E>>> {
+/// Buffer data, shared by [`SourceBuffer`] and
+/// [`SyncSourceBuffer`](super::SyncSourceBuffer).
+pub(super) struct Inner<E, I: Iterator<Item = Result<char, E>>> {
 	/// Input source `char` stream.
-	input: I,
+	input: Peekable<I>,
 
 	/// SourceBuffer error state.
-	error: Option<E>,
+	error: Option<SourceError<E>>,
+
+	/// Buffer data, encoded in UTF-8.
+	///
+	/// The first byte is the byte at index `offset`.
+	data: String,
 
-	/// Buffer data.
-	data: Vec<char>,
+	/// Number of forgotten bytes.
+	offset: usize,
+
+	/// Position of the first character of the buffer data.
+	window: Position,
 
 	/// Lines index.
 	///
-	/// Contains the index of the first character of each line.
+	/// Contains the byte index of the first character of each line, starting
+	/// from the line of `window`.
 	lines: Vec<usize>,
 
-	/// Span of the buffer.
+	/// Span of the buffer, including forgotten characters.
 	span: Span,
 }
 
+/// Iteration state of a buffer iterator.
+pub(super) struct Cursor<E> {
+	/// Byte index of the next character, or the error preventing to find it.
+	i: Option<Result<usize, BufferError<E>>>,
+
+	/// Position of the next character.
+	pos: Position,
+
+	/// End of the iteration.
+	end: Position,
+
+	/// Previously iterated character.
+	previous: Option<char>,
+
+	/// Byte index of the end of the iteration, once iterating backward.
+	back: Option<usize>,
+}
+
+impl<E> Cursor<E> {
+	pub(super) fn new(i: Option<Result<usize, BufferError<E>>>, pos: Position, end: Position) -> Self {
+		Self { i, pos, end, previous: None, back: None }
+	}
+}
+
 impl<E, I: Iterator<Item = Result<char, E>>> Inner<E, I> {
+	/// Create a new empty buffer starting at the given position.
+	pub(super) fn new(input: I, position: Position) -> Self {
+		Self {
+			input: input.peekable(),
+			error: None,
+			data: String::new(),
+			offset: 0,
+			window: position,
+			lines: vec![0],
+			span: position.into(),
+		}
+	}
+
+	/// Get the span of the buffered data.
+	pub(super) const fn span(&self) -> Span { self.span }
+
+	/// Get the position and index of the first character not forgotten.
+	pub(super) const fn window(&self) -> (Position, usize) { (self.window, self.offset) }
+
+	/// Byte index following the last buffered character.
+	fn len(&self) -> usize { self.offset + self.data.len() }
+
+	/// Get the buffered character at the given byte index.
+	fn buffered(&self, i: usize) -> Option<char> {
+		i.checked_sub(self.offset).and_then(|i| self.data.get(i..)).and_then(|data| data.chars().next())
+	}
+
+	/// Take the input stream error, if any.
+	fn take_error(&mut self) -> Result<Option<usize>, BufferError<E>> {
+		match self.error.take() {
+			Some(e) => Err(BufferError::Input(e)),
+			None => Ok(None),
+		}
+	}
+
 	/// Read the next line from the input stream and add it to the buffer.
 	/// Returns `true` if a new line has been added. Returns `false` if the
 	/// source stream is done.
@@ -46,9 +124,21 @@ impl<E, I: Iterator<Item = Result<char, E>>> Inner<E, I> {
 					Some(Ok(c)) => {
 						self.data.push(c);
 						self.span.push(c, metrics);
+
+						// the continuation of a line break sequence is part of this line.
+						// The input is only looked ahead when a continuation is possible,
+						// so that no character past the line is read otherwise.
+						if metrics.line_breaks().may_continue(c) {
+							if let Some(Ok(next)) = self.input.peek() {
+								if metrics.line_breaks().continues(c, *next) {
+									self.data.push(*next);
+									self.input.next();
+								}
+							}
+						}
 					}
 					Some(Err(e)) => {
-						self.error = Some(e);
+						self.error = Some(SourceError::new(self.span.end(), e));
 						return false;
 					}
 					None => return false,
@@ -56,7 +146,7 @@ impl<E, I: Iterator<Item = Result<char, E>>> Inner<E, I> {
 			}
 
 			// register the next line index.
-			self.lines.push(self.data.len());
+			self.lines.push(self.len());
 
 			true
 		} else {
@@ -64,8 +154,23 @@ impl<E, I: Iterator<Item = Result<char, E>>> Inner<E, I> {
 		}
 	}
 
-	/// Get the index of the char at the given cursor position if it is in the
-	/// buffer. If it is not in the buffer but after the buffered content,
+	/// Discard the buffered lines preceding the line of the given position.
+	pub(super) fn forget_before(&mut self, pos: Position) {
+		if pos.line > self.window.line {
+			// only the lines entirely buffered can be forgotten.
+			let count = std::cmp::min(pos.line - self.window.line, self.lines.len() - 1);
+			if count > 0 {
+				let start = self.lines[count];
+				self.data.drain(..(start - self.offset));
+				self.lines.drain(..count);
+				self.offset = start;
+				self.window = Position::new(self.window.line + count, 0);
+			}
+		}
+	}
+
+	/// Get the byte index of the char at the given cursor position if it is in
+	/// the buffer. If it is not in the buffer but after the buffered content,
 	/// the input stream will be read until the buffer span includes the
 	/// given position.
 	///
@@ -73,61 +178,378 @@ impl<E, I: Iterator<Item = Result<char, E>>> Inner<E, I> {
 	/// positions, if the source stream ends before the given position, or
 	/// if the line at the given position is shorter than the given position
 	/// column.
-	fn index_at<M: Metrics>(&mut self, pos: Position, metrics: &M) -> Result<Option<usize>, E> {
+	pub(super) fn index_at<M: Metrics>(&mut self, pos: Position, metrics: &M) -> Result<Option<usize>, BufferError<E>> {
 		if pos < self.span.start() {
 			Ok(None)
+		} else if pos < self.window {
+			Err(BufferError::Forgotten(self.window))
 		} else {
 			while pos >= self.span.end() && self.read_line(metrics) {}
 
-			if pos >= self.span.end() {
-				let mut error = None;
-				std::mem::swap(&mut error, &mut self.error);
-				match error {
-					Some(e) => Err(e),
-					None => Ok(None),
-				}
-			} else {
+			// 0 columns wide characters may be located at the end position.
+			if pos <= self.span.end() {
 				// line index relative to the first line of the buffer.
-				let relative_line = pos.line - self.span.start().line;
+				let relative_line = pos.line - self.window.line;
 				// get the index of the char of the begining of the line in the buffer.
 				let mut i = self.lines[relative_line];
 				// place a virtual cursor at the begining of the target line.
-				let mut cursor = Position::new(pos.line, 0);
+				let mut cursor = if relative_line == 0 {
+					self.window
+				} else {
+					Position::new(pos.line, 0)
+				};
 
 				while cursor < pos {
-					cursor = cursor.next(self.data[i], metrics);
-					i += 1;
+					match self.buffered(i) {
+						Some(c) => {
+							cursor = cursor.next(c, metrics);
+							i += c.len_utf8();
+						}
+						None => break
+					}
 				}
 
-				if cursor == pos {
+				if cursor == pos && i < self.len() {
 					// found it!
-					Ok(Some(i))
-				} else {
-					// the position does not exist in the buffer.
-					Ok(None)
+					return Ok(Some(i))
 				}
 			}
+
+			if pos >= self.span.end() {
+				self.take_error()
+			} else {
+				// the position does not exist in the buffer.
+				Ok(None)
+			}
 		}
 	}
 
-	/// Get the character at the given index.
+	/// Get the char at the given cursor position.
+	pub(super) fn at<M: Metrics>(&mut self, pos: Position, metrics: &M) -> Result<Option<char>, BufferError<E>> {
+		match self.index_at(pos, metrics)? {
+			Some(i) => self.get(i, metrics),
+			None => Ok(None)
+		}
+	}
+
+	/// Get the index of the char at the given cursor position, or the length
+	/// of the buffer if the position is the end of the source stream.
+	fn index_or_end_at<M: Metrics>(&mut self, pos: Position, metrics: &M) -> Result<Option<usize>, BufferError<E>> {
+		match self.index_at(pos, metrics)? {
+			Some(i) => Ok(Some(i)),
+			None if pos == self.span.end() && self.input.peek().is_none() => Ok(Some(self.len())),
+			None => Ok(None)
+		}
+	}
+
+	/// Get the position of the character at the given index in the buffer, or
+	/// the end of the buffer span if the index is the length of the buffer.
+	///
+	/// The index must not be forgotten.
+	fn position<M: Metrics>(&self, i: usize, metrics: &M) -> Position {
+		let relative_line = self.lines.partition_point(|l| *l <= i) - 1;
+		let mut cursor = if relative_line == 0 {
+			self.window
+		} else {
+			Position::new(self.window.line + relative_line, 0)
+		};
+
+		let mut previous = None;
+		for c in self.data[(self.lines[relative_line] - self.offset)..(i - self.offset)].chars() {
+			cursor = cursor.next_after(previous, c, metrics);
+			previous = Some(c)
+		}
+
+		cursor
+	}
+
+	/// Create the span between the two given positions.
+	pub(super) fn span_between<M: Metrics>(&mut self, start: Position, end: Position, metrics: &M) -> Result<Result<Span, SpanError>, BufferError<E>> {
+		if end < start {
+			return Ok(Err(SpanError::Reversed(start, end)))
+		}
+
+		let start_index = match self.index_or_end_at(start, metrics)? {
+			Some(i) => i,
+			None => return Ok(Err(SpanError::InvalidPosition(start)))
+		};
+
+		let end_index = match self.index_or_end_at(end, metrics)? {
+			Some(i) => i,
+			None => return Ok(Err(SpanError::InvalidPosition(end)))
+		};
+
+		if start_index == end_index {
+			return Ok(Ok(start.into()))
+		}
+
+		// find the last character, including multi-characters line breaks.
+		let mut chars = self.data[(start_index - self.offset)..(end_index - self.offset)].char_indices().rev();
+		let (mut last, c) = chars.next().unwrap();
+		if let Some((i, previous)) = chars.next() {
+			if metrics.line_breaks().continues(previous, c) {
+				last = i
+			}
+		}
+
+		Ok(Ok(Span::new(start, self.position(start_index + last, metrics), end)))
+	}
+
+	/// Get the character at the given byte index.
 	///
 	/// If it is not in the buffer but after the buffered content, the input
 	/// stream will be read until the buffer span includes the given
 	/// position. Returns `None` if the source stream ends before the given
 	/// position.
-	fn get<M: Metrics>(&mut self, i: usize, metrics: &M) -> Result<Option<char>, E> {
-		while i >= self.data.len() && self.read_line(metrics) {}
-
-		if i >= self.data.len() {
-			let mut error = None;
-			std::mem::swap(&mut error, &mut self.error);
-			match error {
-				Some(e) => Err(e),
-				None => Ok(None),
+	pub(super) fn get<M: Metrics>(&mut self, i: usize, metrics: &M) -> Result<Option<char>, BufferError<E>> {
+		if i < self.offset {
+			return Err(BufferError::Forgotten(self.window))
+		}
+
+		while i >= self.len() && self.read_line(metrics) {}
+
+		match self.buffered(i) {
+			Some(c) => Ok(Some(c)),
+			None if i >= self.len() => self.take_error().map(|_| None),
+			None => Ok(None)
+		}
+	}
+
+	/// Get the buffered text iterated by [`SourceBuffer::iter_span`].
+	pub(super) fn slice<M: Metrics>(&mut self, span: Span, metrics: &M) -> Result<&str, BufferError<E>> {
+		let range = self.slice_range(span, metrics)?;
+		Ok(&self.data[range])
+	}
+
+	/// Get the range of the buffer data iterated by
+	/// [`SourceBuffer::iter_span`].
+	fn slice_range<M: Metrics>(&mut self, span: Span, metrics: &M) -> Result<Range<usize>, BufferError<E>> {
+		let pos = std::cmp::max(self.span.start(), span.start());
+		let start = match self.index_at(pos, metrics)? {
+			Some(i) => i,
+			None => return Ok(0..0)
+		};
+
+		let end = self.iteration_end(start, pos, None, span.end(), metrics)?;
+		Ok((start - self.offset)..(end - self.offset))
+	}
+
+	/// Find the byte index of the end of an iteration, given the index,
+	/// position and previous character of the next iterated character.
+	///
+	/// The input stream is read until the buffer includes the end position.
+	fn iteration_end<M: Metrics>(&mut self, mut i: usize, mut pos: Position, mut previous: Option<char>, end: Position, metrics: &M) -> Result<usize, BufferError<E>> {
+		if i < self.offset {
+			return Err(BufferError::Forgotten(self.window))
+		}
+
+		while pos < end {
+			match self.buffered(i) {
+				Some(c) => {
+					pos = pos.next_after(previous, c, metrics);
+					previous = Some(c);
+					i += c.len_utf8();
+				}
+				None => if !self.read_line(metrics) {
+					self.take_error()?;
+					break
+				}
+			}
+		}
+
+		// the continuation of a line break sequence ending the span is part of
+		// the span. It is always buffered with the line break.
+		if let (Some(previous), Some(c)) = (previous, self.buffered(i)) {
+			if metrics.line_breaks().continues(previous, c) {
+				i += c.len_utf8()
+			}
+		}
+
+		Ok(i)
+	}
+
+	/// Get the character preceding the character at the given position (or
+	/// the end of the source stream).
+	pub(super) fn char_before<M: Metrics>(&mut self, pos: Position, metrics: &M) -> Result<Option<char>, BufferError<E>> {
+		match self.index_or_end_at(pos, metrics)? {
+			Some(i) if i > self.offset => Ok(self.data[..(i - self.offset)].chars().next_back()),
+			Some(_) if self.offset > 0 => Err(BufferError::Forgotten(self.window)),
+			_ => Ok(None)
+		}
+	}
+
+	/// Read the input stream until the given line is entirely buffered, or
+	/// the stream ends.
+	fn read_until_line<M: Metrics>(&mut self, line: usize, metrics: &M) -> Result<(), BufferError<E>> {
+		while self.span.end().line <= line && self.read_line(metrics) {}
+
+		if self.span.end().line <= line {
+			self.take_error()?;
+		}
+
+		Ok(())
+	}
+
+	/// Get the given buffered line.
+	///
+	/// The line must not be forgotten.
+	fn buffered_line<M: Metrics>(&self, line: usize, metrics: &M) -> Option<Line> {
+		let relative_line = line - self.window.line;
+		let start_index = *self.lines.get(relative_line)?;
+		// the next line is started only if this line ends with a line break.
+		let next_line = self.lines.get(relative_line + 1).copied();
+		let end_index = next_line.unwrap_or_else(|| self.len());
+
+		if start_index == end_index && line > self.span.start().line {
+			return None
+		}
+
+		let start = if relative_line == 0 {
+			self.window
+		} else {
+			Position::new(line, 0)
+		};
+
+		let text = &self.data[(start_index - self.offset)..(end_index - self.offset)];
+
+		// the span is built as the buffer span.
+		let mut span: Span = start.into();
+		let mut previous = None;
+		for c in text.chars() {
+			span.push_after(previous, c, metrics);
+			previous = Some(c)
+		}
+
+		// find the line break, including multi-characters line breaks.
+		let content_len = if next_line.is_some() {
+			let mut chars = text.char_indices().rev();
+			let (mut last, c) = chars.next()?;
+			if let Some((i, previous)) = chars.next() {
+				if metrics.line_breaks().continues(previous, c) {
+					last = i
+				}
+			}
+
+			last
+		} else {
+			text.len()
+		};
+
+		Some(Line::new(line, span, text.to_string(), content_len))
+	}
+
+	/// Get the given line.
+	pub(super) fn line<M: Metrics>(&mut self, line: usize, metrics: &M) -> Result<Option<Line>, BufferError<E>> {
+		if line < self.span.start().line {
+			Ok(None)
+		} else if line < self.window.line {
+			Err(BufferError::Forgotten(self.window))
+		} else {
+			self.read_until_line(line, metrics)?;
+			Ok(self.buffered_line(line, metrics))
+		}
+	}
+
+	/// Get the lines in the given range.
+	pub(super) fn lines<M: Metrics, R: RangeBounds<usize>>(&mut self, range: R, metrics: &M) -> Result<Vec<Line>, BufferError<E>> {
+		let start = match range.start_bound() {
+			Bound::Included(line) => *line,
+			Bound::Excluded(line) => line.saturating_add(1),
+			Bound::Unbounded => 0
+		};
+
+		let end = match range.end_bound() {
+			Bound::Included(line) => line.saturating_add(1),
+			Bound::Excluded(line) => *line,
+			Bound::Unbounded => usize::MAX
+		};
+
+		let start = std::cmp::max(start, self.span.start().line);
+		if start >= end {
+			return Ok(Vec::new())
+		}
+
+		if start < self.window.line {
+			return Err(BufferError::Forgotten(self.window))
+		}
+
+		self.read_until_line(end - 1, metrics)?;
+		Ok((start..end).map_while(|line| self.buffered_line(line, metrics)).collect())
+	}
+
+	/// Get the next character of the given iteration.
+	pub(super) fn next_char<M: Metrics>(&mut self, cursor: &mut Cursor<E>, metrics: &M) -> Option<Result<char, BufferError<E>>> {
+		if let (Some(Ok(i)), Some(back)) = (&cursor.i, cursor.back) {
+			if *i >= back {
+				return None
+			}
+		}
+
+		if cursor.pos >= cursor.end {
+			// the continuation of a line break sequence ending the span is part
+			// of the span. It is always buffered with the line break.
+			if let (Some(previous), Some(Ok(i))) = (cursor.previous, &mut cursor.i) {
+				if let Some(c) = self.buffered(*i) {
+					if metrics.line_breaks().continues(previous, c) {
+						cursor.previous = Some(c);
+						*i += c.len_utf8();
+						return Some(Ok(c))
+					}
+				}
+			}
+
+			None
+		} else {
+			match &mut cursor.i {
+				Some(Ok(ref mut i)) => match self.get(*i, metrics) {
+					Ok(Some(c)) => {
+						cursor.pos = cursor.pos.next_after(cursor.previous, c, metrics);
+						cursor.previous = Some(c);
+						*i += c.len_utf8();
+						Some(Ok(c))
+					}
+					Ok(None) => None,
+					Err(e) => Some(Err(e)),
+				},
+				None => None,
+				ref mut i => {
+					let mut new_i = None;
+					std::mem::swap(&mut new_i, i);
+					if let Some(Err(e)) = new_i {
+						Some(Err(e))
+					} else {
+						unreachable!()
+					}
+				}
+			}
+		}
+	}
+
+	/// Get the next character from the end of the given iteration.
+	pub(super) fn next_back_char<M: Metrics>(&mut self, cursor: &mut Cursor<E>, metrics: &M) -> Option<Result<char, BufferError<E>>> {
+		let i = match &cursor.i {
+			Some(Ok(i)) => *i,
+			// nothing to iterate, or an error to report.
+			_ => return self.next_char(cursor, metrics)
+		};
+
+		let back = match cursor.back {
+			Some(back) => back,
+			None => match self.iteration_end(i, cursor.pos, cursor.previous, cursor.end, metrics) {
+				Ok(back) => back,
+				Err(e) => return Some(Err(e))
 			}
+		};
+
+		if back <= i {
+			cursor.back = Some(back);
+			None
+		} else if back <= self.offset {
+			Some(Err(BufferError::Forgotten(self.window)))
 		} else {
-			Ok(Some(self.data[i]))
+			let c = self.data[..(back - self.offset)].chars().next_back().unwrap();
+			cursor.back = Some(back - c.len_utf8());
+			Some(Ok(c))
 		}
 	}
 }
@@ -136,13 +558,7 @@ impl<E, I: Iterator<Item = Result<char, E>>, M: Metrics> SourceBuffer<E, I, M> {
 	/// Create a new empty buffer starting at the given position.
 	pub fn new(input: I, position: Position, metrics: M) -> Self {
 		Self {
-			p: RefCell::new(Inner {
-				input,
-				error: None,
-				data: Vec::new(),
-				lines: vec![0],
-				span: position.into(),
-			}),
+			p: RefCell::new(Inner::new(input, position)),
 			metrics,
 		}
 	}
@@ -151,10 +567,45 @@ impl<E, I: Iterator<Item = Result<char, E>>, M: Metrics> SourceBuffer<E, I, M> {
 	pub fn metrics(&self) -> &M { &self.metrics }
 
 	/// Get the span of the entire buffered data.
-	pub fn span(&self) -> Span { self.p.borrow().span }
+	pub fn span(&self) -> Span { self.p.borrow().span() }
+
+	/// Get the position of the first character that has not been forgotten.
+	///
+	/// This is the start of the buffer span unless
+	/// [`forget_before`](SourceBuffer::forget_before) has been called.
+	pub fn window_start(&self) -> Position { self.p.borrow().window().0 }
+
+	/// Get the position and byte index of the first character that has not
+	/// been forgotten.
+	pub(super) fn window(&self) -> (Position, usize) { self.p.borrow().window() }
+
+	/// Discard the buffered lines preceding the line of the given position,
+	/// to bound the memory used by the buffer when reading large streams.
+	///
+	/// Only entire lines are discarded, and only the lines that have already
+	/// been read from the input stream. Positions and character indexes are
+	/// not affected: the following characters keep the same position and
+	/// index, and accessing a forgotten character returns a
+	/// [`BufferError::Forgotten`] error.
+	///
+	/// ```
+	/// use source_span::{BufferError, DEFAULT_METRICS, Position, SourceBuffer};
+	///
+	/// let chars = "a\nb\nc\n".chars().map(Ok::<char, ()>);
+	/// let buffer = SourceBuffer::new(chars, Position::default(), DEFAULT_METRICS);
+	///
+	/// assert_eq!(buffer.at(Position::new(1, 0)), Ok(Some('b')));
+	/// buffer.forget_before(Position::new(1, 0));
+	///
+	/// assert_eq!(buffer.window_start(), Position::new(1, 0));
+	/// assert_eq!(buffer.at(Position::new(0, 0)), Err(BufferError::Forgotten(Position::new(1, 0))));
+	/// assert_eq!(buffer.at(Position::new(2, 0)), Ok(Some('c')));
+	/// assert_eq!(buffer.iter().into_string(), Ok("b\nc\n".to_string()));
+	/// ```
+	pub fn forget_before(&self, pos: Position) { self.p.borrow_mut().forget_before(pos) }
 
-	/// Get the index of the char at the given cursor position if it is in the
-	/// buffer. If it is not in the buffer but after the buffered content,
+	/// Get the byte index of the char at the given cursor position if it is in
+	/// the buffer. If it is not in the buffer but after the buffered content,
 	/// the input stream will be read until the buffer span includes the
 	/// given position.
 	///
@@ -162,7 +613,9 @@ impl<E, I: Iterator<Item = Result<char, E>>, M: Metrics> SourceBuffer<E, I, M> {
 	/// positions, if the source stream ends before the given position, or
 	/// if the line at the given position is shorter than the given position
 	/// column.
-	pub fn index_at(&self, pos: Position) -> Result<Option<usize>, E> {
+	/// Returns [`BufferError::Forgotten`] if the position has been forgotten
+	/// (see [`forget_before`](SourceBuffer::forget_before)).
+	pub fn index_at(&self, pos: Position) -> Result<Option<usize>, BufferError<E>> {
 		self.p.borrow_mut().index_at(pos, &self.metrics)
 	}
 
@@ -173,33 +626,179 @@ impl<E, I: Iterator<Item = Result<char, E>>, M: Metrics> SourceBuffer<E, I, M> {
 	/// Returns `None` if the given position is out of range, if the source
 	/// stream ends before the given position, or if the line at the given
 	/// position is shorter than the given position column.
-	pub fn at(&self, pos: Position) -> Result<Option<char>, E> {
-		match self.index_at(pos) {
-			Ok(Some(i)) => self.p.borrow_mut().get(i, &self.metrics),
-			Ok(None) => Ok(None),
-			Err(e) => Err(e)
+	/// Returns [`BufferError::Forgotten`] if the position has been forgotten.
+	pub fn at(&self, pos: Position) -> Result<Option<char>, BufferError<E>> {
+		self.p.borrow_mut().at(pos, &self.metrics)
+	}
+
+	/// Create the span from `start` to `end` (excluded), computing its last
+	/// position from the source text.
+	///
+	/// The input stream is read until the buffer includes `end`.
+	/// Panics if `end` is before `start` or if the positions are not the
+	/// positions of characters (or the end) of the source text.
+	/// See [`try_span_between`](SourceBuffer::try_span_between) for a
+	/// non-panicking version.
+	///
+	/// ```
+	/// use source_span::{DEFAULT_METRICS, Position, SourceBuffer, Span};
+	///
+	/// let chars = "let x =\t42;".chars().map(Ok::<char, ()>);
+	/// let buffer = SourceBuffer::new(chars, Position::default(), DEFAULT_METRICS);
+	///
+	/// let span = buffer.span_between(Position::new(0, 4), Position::new(0, 8)).unwrap();
+	/// assert_eq!(span, Span::new(Position::new(0, 4), Position::new(0, 7), Position::new(0, 8)));
+	/// ```
+	pub fn span_between(&self, start: Position, end: Position) -> Result<Span, BufferError<E>> {
+		match self.try_span_between(start, end)? {
+			Ok(span) => Ok(span),
+			Err(e) => panic!("{}", e)
 		}
 	}
 
-	/// Get the character at the given index.
+	/// Create the span from `start` to `end` (excluded), computing its last
+	/// position from the source text.
+	///
+	/// The input stream is read until the buffer includes `end`.
+	/// Returns an error if `end` is before `start` or if the positions are not
+	/// the positions of characters (or the end) of the source text.
+	/// The outer result holds the errors of the input stream, or
+	/// [`BufferError::Forgotten`] if a position has been forgotten.
+	pub fn try_span_between(&self, start: Position, end: Position) -> Result<Result<Span, SpanError>, BufferError<E>> {
+		self.p.borrow_mut().span_between(start, end, &self.metrics)
+	}
+
+	/// Get the character at the given byte index.
 	///
 	/// If it is not in the buffer but after the buffered content, the input
 	/// stream will be read until the buffer span includes the given
 	/// position. Returns `None` if the source stream ends before the given
-	/// position.
-	pub fn get(&self, i: usize) -> Result<Option<char>, E> { self.p.borrow_mut().get(i, &self.metrics) }
+	/// position or if the index is not the index of a character, and [`BufferError::Forgotten`] if the character has been
+	/// forgotten.
+	pub fn get(&self, i: usize) -> Result<Option<char>, BufferError<E>> { self.p.borrow_mut().get(i, &self.metrics) }
+
+	/// Get the text of the given span.
+	///
+	/// This is the text iterated by [`iter_span`](SourceBuffer::iter_span): the input
+	/// stream is read until the buffer includes the end of the span.
+	/// The text is empty if the span start is not the position of a
+	/// character.
+	/// Returns [`BufferError::Forgotten`] if the start of the span has been
+	/// forgotten.
+	///
+	/// The text is borrowed from the buffer: the buffer cannot be read
+	/// further (by iterating it or accessing other parts of the source) while
+	/// the returned reference is alive, otherwise it panics. Use
+	/// [`slice_to_string`](SourceBuffer::slice_to_string) to get a copy of
+	/// the text instead.
+	///
+	/// ```
+	/// use source_span::{DEFAULT_METRICS, Position, SourceBuffer, Span};
+	///
+	/// let chars = "let x =\t42;".chars().map(Ok::<char, ()>);
+	/// let buffer = SourceBuffer::new(chars, Position::default(), DEFAULT_METRICS);
+	///
+	/// let span = Span::new(Position::new(0, 8), Position::new(0, 9), Position::new(0, 10));
+	/// assert_eq!(&*buffer.slice(span).unwrap(), "42");
+	/// ```
+	pub fn slice(&self, span: Span) -> Result<Ref<'_, str>, BufferError<E>> {
+		let range = self.p.borrow_mut().slice_range(span, &self.metrics)?;
+		Ok(Ref::map(self.p.borrow(), |p| &p.data[range]))
+	}
+
+	/// Get a copy of the text of the given span.
+	///
+	/// This is the same as [`slice`](SourceBuffer::slice), except that the
+	/// text is copied out of the buffer, so that the buffer can be read
+	/// further while the text is in use.
+	///
+	/// ```
+	/// use source_span::{DEFAULT_METRICS, Position, SourceBuffer, Span};
+	///
+	/// let chars = "let x =\t42;".chars().map(Ok::<char, ()>);
+	/// let buffer = SourceBuffer::new(chars, Position::default(), DEFAULT_METRICS);
+	///
+	/// let span = Span::new(Position::new(0, 8), Position::new(0, 9), Position::new(0, 10));
+	/// let text = buffer.slice_to_string(span).unwrap();
+	/// let mut iter = buffer.iter();
+	/// assert_eq!(iter.next(), Some(Ok('l')));
+	/// assert_eq!(text, "42");
+	/// ```
+	pub fn slice_to_string(&self, span: Span) -> Result<String, BufferError<E>> {
+		self.slice(span).map(|text| text.to_string())
+	}
+
+	/// Get the character preceding the character at the given position, or
+	/// preceding the end of the source stream.
+	///
+	/// The input stream is read until the buffer includes the given position.
+	/// Returns `None` if there is no character at the given position (nor
+	/// the end of the source stream), or no character before it.
+	/// Returns [`BufferError::Forgotten`] if the preceding character has been
+	/// forgotten.
+	///
+	/// ```
+	/// use source_span::{DEFAULT_METRICS, Position, SourceBuffer};
+	///
+	/// let chars = "ab\ncd".chars().map(Ok::<char, ()>);
+	/// let buffer = SourceBuffer::new(chars, Position::default(), DEFAULT_METRICS);
+	///
+	/// assert_eq!(buffer.char_before(Position::new(1, 0)), Ok(Some('\n')));
+	/// assert_eq!(buffer.char_before(Position::new(1, 2)), Ok(Some('d')));
+	/// assert_eq!(buffer.char_before(Position::new(0, 0)), Ok(None));
+	/// ```
+	pub fn char_before(&self, pos: Position) -> Result<Option<char>, BufferError<E>> {
+		self.p.borrow_mut().char_before(pos, &self.metrics)
+	}
+
+	/// Get the line with the given index.
+	///
+	/// The input stream is read until the buffer includes the entire line.
+	/// Returns `None` if the line is previous to the buffer start position,
+	/// or if the source stream ends before the line. The line following a
+	/// final line break is not a line.
+	/// Returns [`BufferError::Forgotten`] if the line has been forgotten.
+	///
+	/// ```
+	/// use source_span::{DefaultMetrics, LineBreaks, Position, SourceBuffer, Span};
+	///
+	/// let chars = "fn main() {\r\n}".chars().map(Ok::<char, ()>);
+	/// let metrics = DefaultMetrics::with_line_breaks(LineBreaks::Unicode);
+	/// let buffer = SourceBuffer::new(chars, Position::default(), metrics);
+	///
+	/// let line = buffer.line(0).unwrap().unwrap();
+	/// assert_eq!(line.as_str(), "fn main() {\r\n");
+	/// assert_eq!(line.content(), "fn main() {");
+	/// assert_eq!(line.span(), Span::new(Position::new(0, 0), Position::new(0, 11), Position::new(1, 0)));
+	/// assert_eq!(buffer.line(2), Ok(None));
+	/// ```
+	pub fn line(&self, n: usize) -> Result<Option<Line>, BufferError<E>> {
+		self.p.borrow_mut().line(n, &self.metrics)
+	}
+
+	/// Get the lines in the given range of line indexes.
+	///
+	/// The input stream is read until the buffer includes the last line of
+	/// the range (or until the stream ends for unbounded ranges).
+	/// Lines that are previous to the buffer start position or after the end
+	/// of the source stream are ignored.
+	/// Returns [`BufferError::Forgotten`] if one of the lines has been
+	/// forgotten.
+	pub fn lines<R: RangeBounds<usize>>(&self, range: R) -> Result<Vec<Line>, BufferError<E>> {
+		self.p.borrow_mut().lines(range, &self.metrics)
+	}
 
 	/// Returns an iterator through the characters of the buffer from the
-	/// begining of it.
+	/// begining of it, or from the first character not forgotten.
 	///
 	/// When it reaches the end of the buffer, the buffer will start reading
 	/// from the source stream.
-	pub fn iter(&self) -> Iter<E, I, M> {
+	pub fn iter(&self) -> Iter<'_, E, I, M> {
+		let (pos, i) = self.p.borrow().window();
+
 		Iter {
 			buffer: self,
-			i: Some(Ok(0)),
-			pos: self.p.borrow().span.start(),
-			end: Position::end(),
+			cursor: Cursor::new(Some(Ok(i)), pos, Position::end()),
 		}
 	}
 
@@ -210,15 +809,13 @@ impl<E, I: Iterator<Item = Result<char, E>>, M: Metrics> SourceBuffer<E, I, M> {
 	/// start from the buffer start position.
 	/// When it reaches the end of the buffer, the buffer will start reading
 	/// from the source stream.
-	pub fn iter_from(&self, pos: Position) -> Iter<E, I, M> {
-		let start = self.p.borrow().span.start();
+	pub fn iter_from(&self, pos: Position) -> Iter<'_, E, I, M> {
+		let start = self.span().start();
 		let pos = std::cmp::max(start, pos);
 
 		Iter {
 			buffer: self,
-			i: self.index_at(pos).transpose(),
-			pos,
-			end: Position::end(),
+			cursor: Cursor::new(self.index_at(pos).transpose(), pos, Position::end()),
 		}
 	}
 
@@ -229,34 +826,67 @@ impl<E, I: Iterator<Item = Result<char, E>>, M: Metrics> SourceBuffer<E, I, M> {
 	/// will start from the buffer start position.
 	/// When it reaches the end of the buffer, the buffer will start reading
 	/// from the source stream.
-	pub fn iter_span(&self, span: Span) -> Iter<E, I, M> {
-		let start = self.p.borrow().span.start();
+	pub fn iter_span(&self, span: Span) -> Iter<'_, E, I, M> {
+		let start = self.span().start();
 		let pos = std::cmp::max(start, span.start());
 
 		Iter {
 			buffer: self,
-			i: self.index_at(pos).transpose(),
-			pos,
-			end: span.end(),
+			cursor: Cursor::new(self.index_at(pos).transpose(), pos, span.end()),
 		}
 	}
 }
 
+impl<R: Read, M: Metrics + Clone> SourceBuffer<DecodeError, Decoder<R, M>, M> {
+	/// Create a new empty buffer starting at the given position, decoding the
+	/// given byte stream.
+	///
+	/// Invalid byte sequences produce a [`DecodeError::InvalidSequence`]
+	/// error. To replace them instead, use a [`Decoder`] with the
+	/// [`DecodePolicy::Replace`](crate::DecodePolicy::Replace) policy as input
+	/// stream.
+	///
+	/// ```
+	/// use std::fs::File;
+	/// use source_span::{DEFAULT_METRICS, Encoding, Position, SourceBuffer};
+	///
+	/// let file = File::open("examples/fib.txt").unwrap();
+	/// let buffer = SourceBuffer::from_reader(file, Encoding::Utf8, Position::default(), DEFAULT_METRICS);
+	///
+	/// assert_eq!(buffer.at(Position::new(0, 0)).unwrap(), Some('p'));
+	/// ```
+	pub fn from_reader(reader: R, encoding: Encoding, position: Position, metrics: M) -> Self {
+		Self::new(Decoder::new(reader, encoding, position, metrics.clone()), position, metrics)
+	}
+}
+
 /// Iterator over the characters of a [`SourceBuffer`].
 ///
 /// This iterator is created using the [`SourceBuffer::iter`] method or the
 /// [`SourceBuffer::iter_from`] method. When it reaches the end of the buffer,
 /// the buffer will start reading from the source stream until the stream itself
 /// return `None`.
+///
+/// It can also iterate backward, reading the input stream until the end of
+/// the iteration first:
+///
+/// ```
+/// use source_span::{DEFAULT_METRICS, Position, SourceBuffer, Span};
+///
+/// let chars = "let x = 42;".chars().map(Ok::<char, ()>);
+/// let buffer = SourceBuffer::new(chars, Position::default(), DEFAULT_METRICS);
+///
+/// let span = Span::new(Position::new(0, 4), Position::new(0, 8), Position::new(0, 9));
+/// let reversed: Result<String, _> = buffer.iter_span(span).rev().collect();
+/// assert_eq!(reversed, Ok("4 = x".to_string()));
+/// ```
 pub struct Iter<'b, E, I: 'b + Iterator<Item = Result<char, E>>, M: Metrics> {
 	buffer: &'b SourceBuffer<E, I, M>,
-	i: Option<Result<usize, E>>,
-	pos: Position,
-	end: Position,
+	cursor: Cursor<E>,
 }
 
 impl<'b, E, I: 'b + Iterator<Item = Result<char, E>>, M: Metrics> Iter<'b, E, I, M> {
-	pub fn into_string(self) -> Result<String, E> {
+	pub fn into_string(self) -> Result<String, BufferError<E>> {
 		let mut string = String::new();
 
 		for c in self {
@@ -268,33 +898,264 @@ impl<'b, E, I: 'b + Iterator<Item = Result<char, E>>, M: Metrics> Iter<'b, E, I,
 }
 
 impl<'b, E, I: 'b + Iterator<Item = Result<char, E>>, M: Metrics> Iterator for Iter<'b, E, I, M> {
-	type Item = Result<char, E>;
+	type Item = Result<char, BufferError<E>>;
 
-	fn next(&mut self) -> Option<Result<char, E>> {
-		if self.pos >= self.end {
-			None
-		} else {
-			match &mut self.i {
-				Some(Ok(ref mut i)) => match self.buffer.get(*i) {
-					Ok(Some(c)) => {
-						self.pos = self.pos.next(c, self.buffer.metrics());
-						*i += 1;
-						Some(Ok(c))
-					}
-					Ok(None) => None,
-					Err(e) => Some(Err(e)),
-				},
-				None => None,
-				ref mut i => {
-					let mut new_i = None;
-					std::mem::swap(&mut new_i, i);
-					if let Some(Err(e)) = new_i {
-						Some(Err(e))
-					} else {
-						unreachable!()
-					}
-				}
+	fn next(&mut self) -> Option<Result<char, BufferError<E>>> {
+		self.buffer.p.borrow_mut().next_char(&mut self.cursor, &self.buffer.metrics)
+	}
+}
+
+impl<'b, E, I: 'b + Iterator<Item = Result<char, E>>, M: Metrics> DoubleEndedIterator for Iter<'b, E, I, M> {
+	fn next_back(&mut self) -> Option<Result<char, BufferError<E>>> {
+		self.buffer.p.borrow_mut().next_back_char(&mut self.cursor, &self.buffer.metrics)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{DefaultMetrics, LineBreaks};
+
+	fn buffer(str: &str) -> SourceBuffer<(), impl '_ + Iterator<Item = Result<char, ()>>, DefaultMetrics> {
+		let metrics = DefaultMetrics::with_line_breaks(LineBreaks::Unicode);
+		SourceBuffer::new(str.chars().map(Ok), Position::default(), metrics)
+	}
+
+	#[test]
+	fn span_between() {
+		let buffer = buffer("Hel\r\nl\to\r\n");
+		let p = Position::new;
+
+		assert_eq!(buffer.try_span_between(p(0, 1), p(1, 0)), Ok(Ok(Span::new(p(0, 1), p(0, 3), p(1, 0)))));
+		assert_eq!(buffer.try_span_between(p(1, 0), p(1, 8)), Ok(Ok(Span::new(p(1, 0), p(1, 1), p(1, 8)))));
+		assert_eq!(buffer.try_span_between(p(1, 0), p(2, 0)), Ok(Ok(Span::new(p(1, 0), p(1, 9), p(2, 0)))));
+		assert_eq!(buffer.try_span_between(p(1, 1), p(1, 1)), Ok(Ok(p(1, 1).into())));
+		assert_eq!(buffer.try_span_between(p(1, 1), p(0, 1)), Ok(Err(SpanError::Reversed(p(1, 1), p(0, 1)))));
+		assert_eq!(buffer.try_span_between(p(1, 0), p(1, 3)), Ok(Err(SpanError::InvalidPosition(p(1, 3)))));
+		assert_eq!(buffer.try_span_between(p(1, 0), p(2, 1)), Ok(Err(SpanError::InvalidPosition(p(2, 1)))));
+	}
+
+	#[test]
+	fn span_between_error() {
+		let chars = vec![Ok('a'), Ok('\n'), Ok('b'), Ok('\n'), Err(())];
+		let buffer = SourceBuffer::new(chars.into_iter(), Position::default(), DefaultMetrics::default());
+
+		assert_eq!(buffer.span_between(Position::new(0, 0), Position::new(1, 0)), Ok(Span::new(Position::new(0, 0), Position::new(0, 1), Position::new(1, 0))));
+		assert_eq!(buffer.span_between(Position::new(0, 0), Position::new(2, 0)), Err(BufferError::Input(SourceError::new(Position::new(2, 0), ()))));
+	}
+
+	#[test]
+	fn resume_after_error() {
+		let chars = vec![Ok('a'), Err(0), Ok('\n'), Ok('b'), Err(1), Err(2), Ok('c')];
+		let buffer = SourceBuffer::new(chars.into_iter(), Position::default(), DefaultMetrics::default());
+		let p = Position::new;
+
+		let items: Vec<_> = buffer.iter().collect();
+		assert_eq!(items, [
+			Ok('a'),
+			Err(BufferError::Input(SourceError::new(p(0, 1), 0))),
+			Ok('\n'),
+			Ok('b'),
+			Err(BufferError::Input(SourceError::new(p(1, 1), 1))),
+			Err(BufferError::Input(SourceError::new(p(1, 1), 2))),
+			Ok('c')
+		]);
+
+		assert_eq!(buffer.iter().collect::<Result<String, _>>(), Ok("a\nbc".to_string()));
+		assert_eq!(buffer.try_span_between(p(0, 0), p(1, 2)), Ok(Ok(Span::new(p(0, 0), p(1, 1), p(1, 2)))));
+	}
+
+	#[test]
+	fn index_at() {
+		// `\0` is 0 columns wide, at the end position.
+		let buffer = buffer("a\0");
+		assert_eq!(buffer.index_at(Position::new(0, 1)), Ok(Some(1)));
+		assert_eq!(buffer.at(Position::new(0, 1)), Ok(Some('\0')));
+		assert_eq!(buffer.index_at(Position::new(0, 2)), Ok(None));
+
+		let buffer = SourceBuffer::new("ab\ncd".chars().map(Ok::<char, ()>), Position::new(3, 4), DefaultMetrics::default());
+		assert_eq!(buffer.at(Position::new(3, 5)), Ok(Some('b')));
+		assert_eq!(buffer.at(Position::new(4, 1)), Ok(Some('d')));
+		assert_eq!(buffer.index_at(Position::new(3, 0)), Ok(None));
+	}
+
+	#[test]
+	fn forget_before() {
+		let buffer = buffer("ab\r\ncd\nef");
+		let p = Position::new;
+
+		// nothing is forgotten before the lines are read.
+		buffer.forget_before(p(1, 0));
+		assert_eq!(buffer.window_start(), p(0, 0));
+		assert_eq!(buffer.at(p(1, 1)), Ok(Some('d')));
+
+		buffer.forget_before(p(1, 1));
+		assert_eq!(buffer.window_start(), p(1, 0));
+		assert_eq!(buffer.at(p(0, 1)), Err(BufferError::Forgotten(p(1, 0))));
+		assert_eq!(buffer.get(3), Err(BufferError::Forgotten(p(1, 0))));
+		assert_eq!(buffer.index_at(p(1, 1)), Ok(Some(5)));
+		assert_eq!(buffer.at(p(2, 1)), Ok(Some('f')));
+		assert_eq!(buffer.try_span_between(p(1, 1), p(2, 2)), Ok(Ok(Span::new(p(1, 1), p(2, 1), p(2, 2)))));
+		assert_eq!(buffer.try_span_between(p(0, 0), p(2, 0)), Err(BufferError::Forgotten(p(1, 0))));
+		assert_eq!(buffer.iter().into_string(), Ok("cd\nef".to_string()));
+		assert_eq!(buffer.iter_from(p(0, 0)).next(), Some(Err(BufferError::Forgotten(p(1, 0)))));
+
+		// lines are never remembered.
+		buffer.forget_before(p(0, 0));
+		assert_eq!(buffer.window_start(), p(1, 0));
+
+		// the last line is not terminated, hence not entirely read.
+		buffer.forget_before(Position::end());
+		assert_eq!(buffer.window_start(), p(2, 0));
+		assert_eq!(buffer.iter_span(Span::new(p(2, 0), p(2, 1), p(2, 2))).into_string(), Ok("ef".to_string()));
+		assert_eq!(buffer.span(), Span::new(p(0, 0), p(2, 1), p(2, 2)));
+	}
+
+	#[test]
+	fn utf8() {
+		let buffer = buffer("é字\r\n🦀a");
+		let p = Position::new;
+
+		assert_eq!(buffer.index_at(p(0, 1)), Ok(Some(2)));
+		assert_eq!(buffer.index_at(p(1, 1)), Ok(Some(11)));
+		assert_eq!(buffer.get(5), Ok(Some('\r')));
+		assert_eq!(buffer.get(4), Ok(None));
+		assert_eq!(buffer.get(10), Ok(None));
+		assert_eq!(buffer.try_span_between(p(0, 1), p(1, 0)), Ok(Ok(Span::new(p(0, 1), p(0, 2), p(1, 0)))));
+
+		assert_eq!(buffer.slice(Span::new(p(0, 1), p(0, 2), p(1, 0))).as_deref().ok(), Some("字\r\n"));
+		assert_eq!(buffer.slice(Span::new(p(1, 0), p(1, 1), Position::end())).as_deref().ok(), Some("🦀a"));
+		assert_eq!(buffer.slice_to_string(Span::new(p(0, 3), p(0, 3), p(0, 4))), Ok(String::new()));
+
+		buffer.forget_before(p(1, 0));
+		assert_eq!(buffer.slice(Span::new(p(0, 0), p(0, 0), p(0, 1))).err(), Some(BufferError::Forgotten(p(1, 0))));
+		assert_eq!(buffer.slice_to_string(Span::new(p(0, 0), p(0, 0), p(0, 1))), Err(BufferError::Forgotten(p(1, 0))));
+		assert_eq!(buffer.iter().into_string(), Ok("🦀a".to_string()));
+	}
+
+	#[test]
+	fn from_reader() {
+		let bytes: &[u8] = &[0x00, b'a', 0x00, b'\n', 0x5b, 0x57, 0x00, b'\n', 0xdc, 0x00];
+		let buffer = SourceBuffer::from_reader(bytes, Encoding::Utf16Be, Position::new(3, 0), DefaultMetrics::default());
+
+		assert_eq!(buffer.at(Position::new(4, 0)).unwrap(), Some('字'));
+		match buffer.at(Position::new(5, 0)) {
+			Err(BufferError::Input(e)) => {
+				assert_eq!(e.position(), Position::new(5, 0));
+				let (pos, bytes) = match e.into_error() {
+					DecodeError::InvalidSequence(pos, bytes) => (pos, bytes),
+					DecodeError::Io(e) => panic!("{}", e)
+				};
+				assert_eq!(pos, Position::new(5, 0));
+				assert_eq!(bytes, [0xdc, 0x00]);
 			}
+			_ => panic!("expected an input error")
 		}
 	}
+
+	#[test]
+	fn lines() {
+		let source = SourceBuffer::new("ab\r\n\ncd".chars().map(Ok::<char, ()>), Position::new(2, 1), DefaultMetrics::with_line_breaks(LineBreaks::Unicode));
+		let p = Position::new;
+
+		let lines = source.lines(..).unwrap();
+		let lines: Vec<_> = lines.iter().map(|l| (l.index(), l.span(), l.content(), l.line_break())).collect();
+		assert_eq!(lines, [
+			(2, Span::new(p(2, 1), p(2, 3), p(3, 0)), "ab", "\r\n"),
+			(3, Span::new(p(3, 0), p(3, 0), p(4, 0)), "", "\n"),
+			(4, Span::new(p(4, 0), p(4, 1), p(4, 2)), "cd", "")
+		]);
+
+		assert_eq!(source.lines(3..4).unwrap().len(), 1);
+		assert_eq!(source.lines(0..=2).unwrap().len(), 1);
+		assert_eq!(source.lines(5..).unwrap().len(), 0);
+		assert_eq!(source.line(1), Ok(None));
+		assert_eq!(source.line(5), Ok(None));
+
+		// lines can be accessed while iterating.
+		let mut iter = source.iter();
+		assert_eq!(iter.next(), Some(Ok('a')));
+		assert_eq!(source.line(4).unwrap().map(Line::into_string).as_deref(), Some("cd"));
+		assert_eq!(iter.next(), Some(Ok('b')));
+
+		source.forget_before(p(3, 0));
+		assert_eq!(source.line(2), Err(BufferError::Forgotten(p(3, 0))));
+		assert_eq!(source.lines(..).map(|lines| lines.len()), Err(BufferError::Forgotten(p(3, 0))));
+		assert_eq!(source.line(3).unwrap().unwrap().as_str(), "\n");
+
+		let trailing = buffer("a\n");
+		assert_eq!(trailing.lines(..).unwrap().len(), 1);
+		// `\0` is 0 columns wide, at the end position.
+		let zero_width = buffer("a\0");
+		let line = zero_width.line(0).unwrap().map(|l| l.span());
+		assert_eq!(line, Some(zero_width.span()));
+		let empty = buffer("");
+		assert_eq!(empty.line(0).unwrap().map(|l| l.span()), Some(Span::default()));
+	}
+
+	#[test]
+	fn line_error() {
+		let chars = vec![Ok('a'), Ok('\n'), Ok('b'), Err(())];
+		let buffer = SourceBuffer::new(chars.into_iter(), Position::default(), DefaultMetrics::default());
+
+		assert_eq!(buffer.line(0).unwrap().map(Line::into_string).as_deref(), Some("a\n"));
+		assert_eq!(buffer.line(1).map(|l| l.is_some()), Err(BufferError::Input(SourceError::new(Position::new(1, 1), ()))));
+		assert_eq!(buffer.line(1).unwrap().map(Line::into_string).as_deref(), Some("b"));
+	}
+
+	#[test]
+	fn no_read_ahead() {
+		// the input cannot be read past the second line, like an interactive input.
+		let input = "a\nb\n".chars().map(Ok::<char, ()>).chain(std::iter::from_fn(|| panic!("read ahead")));
+		let buffer = SourceBuffer::new(input, Position::default(), DefaultMetrics::default());
+
+		assert_eq!(buffer.line(0).unwrap().map(Line::into_string).as_deref(), Some("a\n"));
+		assert_eq!(buffer.line(1).unwrap().map(Line::into_string).as_deref(), Some("b\n"));
+	}
+
+	#[test]
+	fn char_before() {
+		let buffer = buffer("a字\r\nb");
+		let p = Position::new;
+
+		assert_eq!(buffer.char_before(p(0, 0)), Ok(None));
+		assert_eq!(buffer.char_before(p(0, 1)), Ok(Some('a')));
+		assert_eq!(buffer.char_before(p(1, 0)), Ok(Some('\n')));
+		assert_eq!(buffer.char_before(p(1, 1)), Ok(Some('b')));
+		assert_eq!(buffer.char_before(p(1, 2)), Ok(None));
+
+		buffer.forget_before(p(1, 0));
+		assert_eq!(buffer.char_before(p(1, 0)), Err(BufferError::Forgotten(p(1, 0))));
+	}
+
+	#[test]
+	fn double_ended() {
+		let buffer = buffer("ab\r\ncd\n");
+		let p = Position::new;
+
+		assert_eq!(buffer.iter().rev().collect::<Result<String, _>>(), Ok("\ndc\n\rba".to_string()));
+
+		let span = Span::new(p(0, 1), p(0, 2), p(1, 0));
+		assert_eq!(buffer.iter_span(span).rev().collect::<Result<String, _>>(), Ok("\n\rb".to_string()));
+
+		let mut iter = buffer.iter_span(Span::new(p(0, 0), p(1, 1), p(1, 2)));
+		assert_eq!(iter.next(), Some(Ok('a')));
+		assert_eq!(iter.next_back(), Some(Ok('d')));
+		assert_eq!(iter.next_back(), Some(Ok('c')));
+		assert_eq!(iter.next(), Some(Ok('b')));
+		assert_eq!(iter.next(), Some(Ok('\r')));
+		assert_eq!(iter.next_back(), Some(Ok('\n')));
+		assert_eq!(iter.next_back(), None);
+		assert_eq!(iter.next(), None);
+
+		// invalid start position.
+		assert_eq!(buffer.iter_span(Span::new(p(0, 5), p(0, 5), p(0, 6))).next_back(), None);
+	}
+
+	#[test]
+	fn iter_span_line_break() {
+		let buffer = buffer("a\r\nb");
+		let span = Span::new(Position::new(0, 0), Position::new(0, 1), Position::new(1, 0));
+		assert_eq!(buffer.iter_span(span).into_string(), Ok("a\r\n".to_string()));
+	}
 }
@@ -0,0 +1,54 @@
+use crate::Span;
+
+/// Line of a source buffer.
+///
+/// Returned by [`SourceBuffer::line`](crate::SourceBuffer::line) and
+/// [`SourceBuffer::lines`](crate::SourceBuffer::lines). It holds a copy of the
+/// line text, so that it can be used while the buffer is read further.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+	/// Line index.
+	index: usize,
+
+	/// Span of the line, including its line break.
+	span: Span,
+
+	/// Text of the line, including its line break.
+	text: String,
+
+	/// Byte length of the line without its line break.
+	content_len: usize,
+}
+
+impl Line {
+	pub(super) const fn new(index: usize, span: Span, text: String, content_len: usize) -> Self {
+		Self { index, span, text, content_len }
+	}
+
+	/// Get the index of the line (starting from 0).
+	#[must_use]
+	pub const fn index(&self) -> usize { self.index }
+
+	/// Get the span of the line, including its line break.
+	#[must_use]
+	pub const fn span(&self) -> Span { self.span }
+
+	/// Get the text of the line, including its line break.
+	#[must_use]
+	pub fn as_str(&self) -> &str { &self.text }
+
+	/// Get the text of the line, without its line break.
+	#[must_use]
+	pub fn content(&self) -> &str { &self.text[..self.content_len] }
+
+	/// Get the line break ending the line.
+	///
+	/// This is empty for the last line of the source, if it is not terminated
+	/// by a line break.
+	#[must_use]
+	pub fn line_break(&self) -> &str { &self.text[self.content_len..] }
+
+	/// Unwrap the text of the line, including its line break.
+	#[must_use]
+	pub fn into_string(self) -> String { self.text }
+}
@@ -1,2 +1,127 @@
+use std::fmt;
+
+use crate::{Position, Span};
+
 mod lazy;
+mod line;
+mod scanner;
+mod sync;
 pub use lazy::*;
+pub use line::*;
+pub use scanner::*;
+pub use sync::*;
+
+/// Input stream error, located at the position where the stream failed.
+///
+/// The located error can be rendered with the
+/// [`Formatter`](crate::fmt::Formatter) by highlighting its
+/// [`span`](SourceError::span):
+///
+/// ```
+/// use source_span::{BufferError, DEFAULT_METRICS, Position, SourceBuffer, Span};
+/// use source_span::fmt::{Formatter, Style};
+///
+/// let chars = vec![Ok('a'), Ok('b'), Err("invalid character"), Ok('c')];
+/// let buffer = SourceBuffer::new(chars.into_iter(), Position::default(), DEFAULT_METRICS);
+///
+/// let error = match buffer.iter().collect::<Result<String, _>>() {
+/// 	Err(BufferError::Input(error)) => error,
+/// 	_ => unreachable!()
+/// };
+///
+/// assert_eq!(error.position(), Position::new(0, 2));
+///
+/// let mut fmt = Formatter::new();
+/// fmt.add(error.span(), Some(error.error().to_string()), Style::Error);
+/// let span = Span::new(Position::new(0, 0), Position::new(0, 1), error.position());
+/// let formatted = fmt.render(buffer.iter_span(span), span, &DEFAULT_METRICS).unwrap();
+/// assert_eq!(formatted.to_plain_string(), "\n1 | ab\n  |   ^ invalid character\n");
+///
+/// // reading can be resumed after the error.
+/// assert_eq!(buffer.at(Position::new(0, 2)), Ok(Some('c')));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SourceError<E> {
+	position: Position,
+	error: E,
+}
+
+impl<E> SourceError<E> {
+	/// Locate the given error at the given position.
+	pub const fn new(position: Position, error: E) -> Self {
+		Self { position, error }
+	}
+
+	/// Get the position where the input stream failed.
+	pub const fn position(&self) -> Position { self.position }
+
+	/// Get the empty span at the position where the input stream failed.
+	pub fn span(&self) -> Span { self.position.into() }
+
+	/// Get the error.
+	pub const fn error(&self) -> &E { &self.error }
+
+	/// Unwrap the error.
+	pub fn into_error(self) -> E { self.error }
+
+	/// Maps the error using the given function.
+	pub fn map<F, U>(self, f: F) -> SourceError<U> where F: FnOnce(E) -> U {
+		SourceError {
+			position: self.position,
+			error: f(self.error),
+		}
+	}
+}
+
+impl<E: fmt::Display> fmt::Display for SourceError<E> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} at {}", self.error, self.position)
+	}
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for SourceError<E> {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { Some(&self.error) }
+}
+
+/// Source buffer access error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferError<E> {
+	/// The input stream failed.
+	///
+	/// The buffer can still be used after the error: the input stream is read
+	/// again by the next accesses, so reading resumes if the input stream
+	/// recovers from the error.
+	Input(SourceError<E>),
+
+	/// The accessed character has been discarded from the buffer using
+	/// [`SourceBuffer::forget_before`].
+	///
+	/// Holds the position of the first character still in the buffer.
+	Forgotten(Position),
+}
+
+impl<E: fmt::Display> fmt::Display for BufferError<E> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			BufferError::Input(e) => e.fmt(f),
+			BufferError::Forgotten(start) => write!(f, "characters before {} have been forgotten", start)
+		}
+	}
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for BufferError<E> {}
+
+impl<E: Into<std::io::Error>> From<BufferError<E>> for std::io::Error {
+	fn from(e: BufferError<E>) -> Self {
+		match e {
+			BufferError::Input(e) => {
+				let e = e.map(Into::<std::io::Error>::into);
+				std::io::Error::new(e.error().kind(), e)
+			}
+			BufferError::Forgotten(start) => std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				BufferError::<std::convert::Infallible>::Forgotten(start).to_string()
+			)
+		}
+	}
+}
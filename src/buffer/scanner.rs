@@ -0,0 +1,329 @@
+use super::{BufferError, SourceBuffer};
+use crate::{Loc, Metrics, Position, Span};
+
+/// Lexer cursor over the characters of a [`SourceBuffer`].
+///
+/// The scanner keeps track of the current position and of the span of the
+/// token being scanned, using the metrics of the buffer. Characters are added
+/// to the current token with [`bump`](Scanner::bump) and
+/// [`eat_while`](Scanner::eat_while), and the token is emitted with
+/// [`take_token`](Scanner::take_token) or discarded with
+/// [`skip_token`](Scanner::skip_token).
+///
+/// ```
+/// use source_span::{DEFAULT_METRICS, Position, Scanner, SourceBuffer};
+///
+/// let chars = "let x = 42;".chars().map(Ok::<char, ()>);
+/// let mut scanner = Scanner::new(SourceBuffer::new(chars, Position::default(), DEFAULT_METRICS));
+///
+/// let mut tokens = Vec::new();
+/// while let Some(c) = scanner.peek(0).unwrap() {
+/// 	if c.is_whitespace() {
+/// 		scanner.eat_while(char::is_whitespace).unwrap();
+/// 		scanner.skip_token();
+/// 	} else {
+/// 		if c.is_alphanumeric() {
+/// 			scanner.eat_while(char::is_alphanumeric).unwrap();
+/// 		} else {
+/// 			scanner.bump().unwrap();
+/// 		}
+///
+/// 		tokens.push(scanner.take_token().unwrap());
+/// 	}
+/// }
+///
+/// let tokens: Vec<_> = tokens.iter().map(|t| (t.as_str(), t.span().start())).collect();
+/// assert_eq!(tokens, [
+/// 	("let", Position::new(0, 0)),
+/// 	("x", Position::new(0, 4)),
+/// 	("=", Position::new(0, 6)),
+/// 	("42", Position::new(0, 8)),
+/// 	(";", Position::new(0, 10))
+/// ]);
+/// ```
+///
+/// Every character read is kept in the underlying buffer. When scanning large
+/// streams, the lines preceding the current token can be discarded using
+/// [`SourceBuffer::forget_before`] on the [`buffer`](Scanner::buffer).
+pub struct Scanner<E, I: Iterator<Item = Result<char, E>>, M: Metrics> {
+	buffer: SourceBuffer<E, I, M>,
+
+	/// Scanning state.
+	state: ScannerMark,
+}
+
+/// Scanner state saved by [`Scanner::mark`], to backtrack using
+/// [`Scanner::reset`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScannerMark {
+	/// Byte index of the next character.
+	index: usize,
+
+	/// Previous character, to detect line break sequences.
+	previous: Option<char>,
+
+	/// Byte index of the start of the current token.
+	token_index: usize,
+
+	/// Span of the current token.
+	///
+	/// Its end is the current position.
+	token: Span,
+}
+
+impl ScannerMark {
+	/// Get the position of the scanner when the mark was created.
+	#[must_use]
+	pub const fn position(&self) -> Position { self.token.end() }
+}
+
+impl<E, I: Iterator<Item = Result<char, E>>, M: Metrics> Scanner<E, I, M> {
+	/// Create a new scanner starting at the first character of the given
+	/// buffer that has not been forgotten (see
+	/// [`SourceBuffer::forget_before`]).
+	pub fn new(buffer: SourceBuffer<E, I, M>) -> Self {
+		let (start, index) = buffer.window();
+		Self {
+			buffer,
+			state: ScannerMark {
+				index,
+				previous: None,
+				token_index: index,
+				token: start.into(),
+			},
+		}
+	}
+
+	/// Get the scanned buffer.
+	pub const fn buffer(&self) -> &SourceBuffer<E, I, M> { &self.buffer }
+
+	/// Unwrap the scanned buffer.
+	pub fn into_buffer(self) -> SourceBuffer<E, I, M> { self.buffer }
+
+	/// Get the position of the next character.
+	pub const fn position(&self) -> Position { self.state.token.end() }
+
+	/// Get the span of the current token.
+	pub const fn token_span(&self) -> Span { self.state.token }
+
+	/// Get the `n`-th next character, without moving the scanner.
+	///
+	/// `peek(0)` is the next character. Returns `None` if the source stream
+	/// ends before.
+	///
+	/// This takes `O(n)` time, since the characters are decoded from the
+	/// current position on each call.
+	pub fn peek(&self, n: usize) -> Result<Option<char>, BufferError<E>> {
+		let mut i = self.state.index;
+		for _ in 0..n {
+			match self.buffer.get(i)? {
+				Some(c) => i += c.len_utf8(),
+				None => return Ok(None)
+			}
+		}
+
+		self.buffer.get(i)
+	}
+
+	/// Add the next character to the current token, and return it.
+	///
+	/// Returns `None` without moving if the source stream ends.
+	pub fn bump(&mut self) -> Result<Option<char>, BufferError<E>> {
+		let c = self.buffer.get(self.state.index)?;
+		if let Some(c) = c {
+			let metrics = self.buffer.metrics();
+			let state = &mut self.state;
+
+			// line break sequences are pushed once, as in the buffer span.
+			state.token.push_after(state.previous, c, metrics);
+			state.previous = Some(c);
+			state.index += c.len_utf8();
+		}
+
+		Ok(c)
+	}
+
+	/// Add the next characters to the current token as long as they satisfy
+	/// the given predicate.
+	///
+	/// Returns the number of characters added.
+	pub fn eat_while<P: FnMut(char) -> bool>(&mut self, mut predicate: P) -> Result<usize, BufferError<E>> {
+		let mut count = 0;
+		while let Some(c) = self.peek(0)? {
+			if !predicate(c) {
+				break
+			}
+
+			self.bump()?;
+			count += 1;
+		}
+
+		Ok(count)
+	}
+
+	/// Save the current state of the scanner, including the current token.
+	pub const fn mark(&self) -> ScannerMark { self.state }
+
+	/// Restore the state saved with [`mark`](Scanner::mark).
+	///
+	/// Marks can be restored in any order, even after taking tokens, as long
+	/// as their position has not been forgotten by the buffer.
+	///
+	/// ```
+	/// use source_span::{DEFAULT_METRICS, Position, Scanner, SourceBuffer};
+	///
+	/// let chars = "1.5".chars().map(Ok::<char, ()>);
+	/// let mut scanner = Scanner::new(SourceBuffer::new(chars, Position::default(), DEFAULT_METRICS));
+	///
+	/// scanner.eat_while(char::is_numeric).unwrap();
+	/// let mark = scanner.mark();
+	/// scanner.bump().unwrap();
+	/// if scanner.eat_while(char::is_alphabetic).unwrap() > 0 {
+	/// 	// method call.
+	/// 	scanner.reset(mark);
+	/// } else {
+	/// 	scanner.eat_while(char::is_numeric).unwrap();
+	/// }
+	///
+	/// assert_eq!(scanner.take_token().unwrap().as_str(), "1.5");
+	/// ```
+	pub fn reset(&mut self, mark: ScannerMark) { self.state = mark }
+
+	/// Emit the current token and start a new one at the current position.
+	///
+	/// Returns [`BufferError::Forgotten`] if the start of the token has been
+	/// forgotten by the buffer.
+	pub fn take_token(&mut self) -> Result<Loc<String>, BufferError<E>> {
+		let mut string = String::new();
+		let mut i = self.state.token_index;
+		while i < self.state.index {
+			// the token characters have already been read.
+			let c = self.buffer.get(i)?.unwrap();
+			string.push(c);
+			i += c.len_utf8()
+		}
+
+		let span = self.state.token;
+		self.skip_token();
+		Ok(Loc::new(string, span))
+	}
+
+	/// Discard the current token and start a new one at the current position.
+	pub fn skip_token(&mut self) {
+		self.state.token_index = self.state.index;
+		self.state.token.clear()
+	}
+}
+
+impl<E, I: Iterator<Item = Result<char, E>>, M: Metrics> From<SourceBuffer<E, I, M>> for Scanner<E, I, M> {
+	fn from(buffer: SourceBuffer<E, I, M>) -> Self { Self::new(buffer) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{DefaultMetrics, LineBreaks};
+
+	type TestScanner = Scanner<(), std::vec::IntoIter<Result<char, ()>>, DefaultMetrics>;
+
+	fn scanner(s: &str) -> TestScanner {
+		let chars: Vec<_> = s.chars().map(Ok).collect();
+		let metrics = DefaultMetrics::with_line_breaks(LineBreaks::Unicode);
+		Scanner::new(SourceBuffer::new(chars.into_iter(), Position::default(), metrics))
+	}
+
+	fn p(line: usize, column: usize) -> Position { Position::new(line, column) }
+
+	#[test]
+	fn peek() {
+		let scanner = scanner("aé\tb");
+		assert_eq!(scanner.peek(0), Ok(Some('a')));
+		assert_eq!(scanner.peek(1), Ok(Some('é')));
+		assert_eq!(scanner.peek(3), Ok(Some('b')));
+		assert_eq!(scanner.peek(4), Ok(None));
+		assert_eq!(scanner.peek(10), Ok(None));
+		assert_eq!(scanner.position(), p(0, 0));
+	}
+
+	#[test]
+	fn tokens() {
+		let mut scanner = scanner("ab\t c\r\nd");
+		assert_eq!(scanner.eat_while(char::is_alphabetic), Ok(2));
+		let token = scanner.take_token().unwrap();
+		assert_eq!(token.as_str(), "ab");
+		assert_eq!(token.span(), Span::new(p(0, 0), p(0, 1), p(0, 2)));
+
+		assert_eq!(scanner.eat_while(char::is_whitespace), Ok(2));
+		assert_eq!(scanner.position(), p(0, 9));
+		scanner.skip_token();
+
+		assert_eq!(scanner.bump(), Ok(Some('c')));
+		assert_eq!(scanner.bump(), Ok(Some('\r')));
+		assert_eq!(scanner.bump(), Ok(Some('\n')));
+		let token = scanner.take_token().unwrap();
+		assert_eq!(token.as_str(), "c\r\n");
+		assert_eq!(token.span(), Span::new(p(0, 9), p(0, 10), p(1, 0)));
+
+		assert_eq!(scanner.bump(), Ok(Some('d')));
+		assert_eq!(scanner.bump(), Ok(None));
+		assert_eq!(scanner.position(), p(1, 1));
+		let token = scanner.take_token().unwrap();
+		assert_eq!(token.as_str(), "d");
+		assert_eq!(token.span(), scanner.buffer().span_between(p(1, 0), p(1, 1)).unwrap());
+
+		let token = scanner.take_token().unwrap();
+		assert_eq!(token.as_str(), "");
+		assert_eq!(token.span(), Span::from(p(1, 1)));
+	}
+
+	#[test]
+	fn forgotten() {
+		let buffer = SourceBuffer::new("a\nbc".chars().map(Ok::<char, ()>), Position::default(), DefaultMetrics::default());
+		assert_eq!(buffer.at(p(1, 0)), Ok(Some('b')));
+		buffer.forget_before(p(1, 0));
+
+		let mut scanner = Scanner::new(buffer);
+		assert_eq!(scanner.position(), p(1, 0));
+		assert_eq!(scanner.peek(1), Ok(Some('c')));
+		scanner.eat_while(|_| true).unwrap();
+		let token = scanner.take_token().unwrap();
+		assert_eq!(token.as_str(), "bc");
+		assert_eq!(token.span(), Span::new(p(1, 0), p(1, 1), p(1, 2)));
+	}
+
+	#[test]
+	fn reset() {
+		let mut scanner = scanner("abc");
+		let start = scanner.mark();
+		assert_eq!(start.position(), p(0, 0));
+		scanner.bump().unwrap();
+		let mark = scanner.mark();
+		scanner.bump().unwrap();
+		assert_eq!(scanner.take_token().unwrap().as_str(), "ab");
+
+		scanner.reset(mark);
+		assert_eq!(scanner.token_span(), Span::new(p(0, 0), p(0, 0), p(0, 1)));
+		scanner.eat_while(|_| true).unwrap();
+		assert_eq!(scanner.take_token().unwrap().as_str(), "abc");
+
+		scanner.reset(start);
+		assert_eq!(scanner.bump(), Ok(Some('a')));
+	}
+
+	#[test]
+	fn error() {
+		let chars = vec![Ok('a'), Err("invalid"), Ok('b')];
+		let mut scanner = Scanner::new(SourceBuffer::new(chars.into_iter(), Position::default(), DefaultMetrics::default()));
+
+		match scanner.eat_while(|_| true) {
+			Err(BufferError::Input(e)) => assert_eq!((e.position(), *e.error()), (p(0, 1), "invalid")),
+			_ => panic!("expected an input error")
+		}
+
+		assert_eq!(scanner.position(), p(0, 1));
+
+		// scanning resumes after the error.
+		assert_eq!(scanner.eat_while(|_| true), Ok(1));
+		assert_eq!(scanner.take_token().unwrap().as_str(), "ab");
+	}
+}
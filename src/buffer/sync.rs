@@ -0,0 +1,373 @@
+use std::io::Read;
+use std::ops::RangeBounds;
+use std::sync::{Mutex, MutexGuard};
+
+use super::lazy::{Cursor, Inner};
+use super::{BufferError, Line};
+use crate::{Decoder, DecodeError, Encoding, Metrics, Position, Span, SpanError};
+
+/// Thread-safe lazy string buffer that fills up on demand, can be iterated and
+/// indexed by character position.
+///
+/// This is the thread-safe version of [`SourceBuffer`](crate::SourceBuffer),
+/// with the same API.
+/// The buffer is protected by a [`Mutex`], so that it can be shared between
+/// threads (provided the input stream and its errors can be sent between
+/// threads), for instance to render many diagnostics in parallel from a
+/// lazily loaded source file.
+///
+/// ```
+/// use source_span::{DEFAULT_METRICS, Position, Span, SyncSourceBuffer};
+/// use source_span::fmt::{Formatter, Style};
+///
+/// let chars = "fn main() {\n\tprintln!(\"Hello\")\n}\n".chars().map(Ok::<char, ()>);
+/// let buffer = SyncSourceBuffer::new(chars, Position::default(), DEFAULT_METRICS);
+///
+/// let spans = [
+/// 	Span::new(Position::new(0, 3), Position::new(0, 6), Position::new(0, 7)),
+/// 	Span::new(Position::new(1, 8), Position::new(1, 15), Position::new(1, 16))
+/// ];
+///
+/// let diagnostics: Vec<String> = std::thread::scope(|s| {
+/// 	let threads: Vec<_> = spans.iter().map(|span| {
+/// 		let buffer = &buffer;
+/// 		s.spawn(move || {
+/// 			let mut fmt = Formatter::new();
+/// 			fmt.add(*span, None, Style::Error);
+/// 			// render the highlighted line.
+/// 			let line = buffer.line(span.start().line).unwrap().unwrap();
+/// 			fmt.render(line.as_str().chars().map(Ok::<char, ()>), line.span(), &DEFAULT_METRICS).unwrap().to_plain_string()
+/// 		})
+/// 	}).collect();
+///
+/// 	threads.into_iter().map(|t| t.join().unwrap()).collect()
+/// });
+///
+/// assert!(diagnostics[0].contains("1 | fn main() {\n  |    ^^^^\n"));
+/// ```
+pub struct SyncSourceBuffer<E, I: Iterator<Item = Result<char, E>>, M: Metrics> {
+	p: Mutex<Inner<E, I>>,
+
+	/// Metrics used.
+	metrics: M,
+}
+
+impl<E, I: Iterator<Item = Result<char, E>>, M: Metrics> SyncSourceBuffer<E, I, M> {
+	/// Create a new empty buffer starting at the given position.
+	pub fn new(input: I, position: Position, metrics: M) -> Self {
+		Self {
+			p: Mutex::new(Inner::new(input, position)),
+			metrics,
+		}
+	}
+
+	/// Lock the buffer.
+	///
+	/// The buffer data is only ever appended to or discarded by entire lines,
+	/// so it stays consistent even if a thread panicked while holding the
+	/// lock.
+	fn lock(&self) -> MutexGuard<'_, Inner<E, I>> {
+		self.p.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+	}
+
+	/// Get the metrics used by the source buffer to map every character.
+	pub fn metrics(&self) -> &M { &self.metrics }
+
+	/// Get the span of the entire buffered data.
+	pub fn span(&self) -> Span { self.lock().span() }
+
+	/// Get the position of the first character that has not been forgotten.
+	///
+	/// This is the start of the buffer span unless
+	/// [`forget_before`](SyncSourceBuffer::forget_before) has been called.
+	pub fn window_start(&self) -> Position { self.lock().window().0 }
+
+	/// Discard the buffered lines preceding the line of the given position,
+	/// to bound the memory used by the buffer when reading large streams.
+	///
+	/// Only entire lines are discarded, and only the lines that have already
+	/// been read from the input stream. Positions and character indexes are
+	/// not affected: the following characters keep the same position and
+	/// index, and accessing a forgotten character returns a
+	/// [`BufferError::Forgotten`] error.
+	pub fn forget_before(&self, pos: Position) { self.lock().forget_before(pos) }
+
+	/// Get the byte index of the char at the given cursor position if it is in
+	/// the buffer. If it is not in the buffer but after the buffered content,
+	/// the input stream will be read until the buffer span includes the
+	/// given position.
+	///
+	/// Returns `None` if the given position if previous to the buffer start
+	/// positions, if the source stream ends before the given position, or
+	/// if the line at the given position is shorter than the given position
+	/// column.
+	/// Returns [`BufferError::Forgotten`] if the position has been forgotten
+	/// (see [`forget_before`](SyncSourceBuffer::forget_before)).
+	pub fn index_at(&self, pos: Position) -> Result<Option<usize>, BufferError<E>> {
+		self.lock().index_at(pos, &self.metrics)
+	}
+
+	/// Get the char at the given position if it is in the buffer.
+	/// If it is not in the buffer yet, the input stream will be pulled until
+	/// the buffer span includes the given position.
+	///
+	/// Returns `None` if the given position is out of range, if the source
+	/// stream ends before the given position, or if the line at the given
+	/// position is shorter than the given position column.
+	/// Returns [`BufferError::Forgotten`] if the position has been forgotten.
+	pub fn at(&self, pos: Position) -> Result<Option<char>, BufferError<E>> {
+		self.lock().at(pos, &self.metrics)
+	}
+
+	/// Create the span from `start` to `end` (excluded), computing its last
+	/// position from the source text.
+	///
+	/// The input stream is read until the buffer includes `end`.
+	/// Panics if `end` is before `start` or if the positions are not the
+	/// positions of characters (or the end) of the source text.
+	/// See [`try_span_between`](SyncSourceBuffer::try_span_between) for a
+	/// non-panicking version.
+	pub fn span_between(&self, start: Position, end: Position) -> Result<Span, BufferError<E>> {
+		match self.try_span_between(start, end)? {
+			Ok(span) => Ok(span),
+			Err(e) => panic!("{}", e)
+		}
+	}
+
+	/// Create the span from `start` to `end` (excluded), computing its last
+	/// position from the source text.
+	///
+	/// The input stream is read until the buffer includes `end`.
+	/// Returns an error if `end` is before `start` or if the positions are not
+	/// the positions of characters (or the end) of the source text.
+	/// The outer result holds the errors of the input stream, or
+	/// [`BufferError::Forgotten`] if a position has been forgotten.
+	pub fn try_span_between(&self, start: Position, end: Position) -> Result<Result<Span, SpanError>, BufferError<E>> {
+		self.lock().span_between(start, end, &self.metrics)
+	}
+
+	/// Get the character at the given byte index.
+	///
+	/// If it is not in the buffer but after the buffered content, the input
+	/// stream will be read until the buffer span includes the given
+	/// position. Returns `None` if the source stream ends before the given
+	/// position or if the index is not the index of a character, and [`BufferError::Forgotten`] if the character has been
+	/// forgotten.
+	pub fn get(&self, i: usize) -> Result<Option<char>, BufferError<E>> { self.lock().get(i, &self.metrics) }
+
+	/// Get the text of the given span.
+	///
+	/// This is the text iterated by [`iter_span`](SyncSourceBuffer::iter_span): the input
+	/// stream is read until the buffer includes the end of the span.
+	/// The text is empty if the span start is not the position of a
+	/// character.
+	/// Returns [`BufferError::Forgotten`] if the start of the span has been
+	/// forgotten.
+	///
+	/// The text is borrowed from the buffer, which requires a mutable
+	/// reference to it: no lock guard can be kept alive in the returned text.
+	/// Use [`slice_to_string`](SyncSourceBuffer::slice_to_string) to get the
+	/// text of a buffer shared between threads.
+	pub fn slice(&mut self, span: Span) -> Result<&str, BufferError<E>> {
+		self.p.get_mut().unwrap_or_else(std::sync::PoisonError::into_inner).slice(span, &self.metrics)
+	}
+
+	/// Get a copy of the text of the given span.
+	///
+	/// This is the same as [`slice`](SyncSourceBuffer::slice), except that
+	/// the text is copied out of the buffer while it is locked, so that it
+	/// only requires a shared reference to the buffer.
+	pub fn slice_to_string(&self, span: Span) -> Result<String, BufferError<E>> {
+		self.lock().slice(span, &self.metrics).map(str::to_string)
+	}
+
+	/// Get the character preceding the character at the given position, or
+	/// preceding the end of the source stream.
+	///
+	/// The input stream is read until the buffer includes the given position.
+	/// Returns `None` if there is no character at the given position (nor
+	/// the end of the source stream), or no character before it.
+	/// Returns [`BufferError::Forgotten`] if the preceding character has been
+	/// forgotten.
+	pub fn char_before(&self, pos: Position) -> Result<Option<char>, BufferError<E>> {
+		self.lock().char_before(pos, &self.metrics)
+	}
+
+	/// Get the line with the given index.
+	///
+	/// The input stream is read until the buffer includes the entire line.
+	/// Returns `None` if the line is previous to the buffer start position,
+	/// or if the source stream ends before the line. The line following a
+	/// final line break is not a line.
+	/// Returns [`BufferError::Forgotten`] if the line has been forgotten.
+	pub fn line(&self, n: usize) -> Result<Option<Line>, BufferError<E>> {
+		self.lock().line(n, &self.metrics)
+	}
+
+	/// Get the lines in the given range of line indexes.
+	///
+	/// The input stream is read until the buffer includes the last line of
+	/// the range (or until the stream ends for unbounded ranges).
+	/// Lines that are previous to the buffer start position or after the end
+	/// of the source stream are ignored.
+	/// Returns [`BufferError::Forgotten`] if one of the lines has been
+	/// forgotten.
+	pub fn lines<R: RangeBounds<usize>>(&self, range: R) -> Result<Vec<Line>, BufferError<E>> {
+		self.lock().lines(range, &self.metrics)
+	}
+
+	/// Returns an iterator through the characters of the buffer from the
+	/// begining of it, or from the first character not forgotten.
+	///
+	/// When it reaches the end of the buffer, the buffer will start reading
+	/// from the source stream.
+	pub fn iter(&self) -> SyncIter<'_, E, I, M> {
+		let (pos, i) = self.lock().window();
+
+		SyncIter {
+			buffer: self,
+			cursor: Cursor::new(Some(Ok(i)), pos, Position::end()),
+		}
+	}
+
+	/// Returns an iterator through the characters of the buffer from the given
+	/// position.
+	///
+	/// If the input position precedes the buffer start position, then it will
+	/// start from the buffer start position.
+	/// When it reaches the end of the buffer, the buffer will start reading
+	/// from the source stream.
+	pub fn iter_from(&self, pos: Position) -> SyncIter<'_, E, I, M> {
+		let pos = std::cmp::max(self.span().start(), pos);
+
+		SyncIter {
+			buffer: self,
+			cursor: Cursor::new(self.index_at(pos).transpose(), pos, Position::end()),
+		}
+	}
+
+	/// Returns an iterator through the characters of the buffer in the given
+	/// span.
+	///
+	/// If the input start position precedes the buffer start position, then it
+	/// will start from the buffer start position.
+	/// When it reaches the end of the buffer, the buffer will start reading
+	/// from the source stream.
+	pub fn iter_span(&self, span: Span) -> SyncIter<'_, E, I, M> {
+		let pos = std::cmp::max(self.span().start(), span.start());
+
+		SyncIter {
+			buffer: self,
+			cursor: Cursor::new(self.index_at(pos).transpose(), pos, span.end()),
+		}
+	}
+}
+
+impl<R: Read, M: Metrics + Clone> SyncSourceBuffer<DecodeError, Decoder<R, M>, M> {
+	/// Create a new empty buffer starting at the given position, decoding the
+	/// given byte stream.
+	///
+	/// Invalid byte sequences produce a [`DecodeError::InvalidSequence`]
+	/// error. To replace them instead, use a [`Decoder`] with the
+	/// [`DecodePolicy::Replace`](crate::DecodePolicy::Replace) policy as input
+	/// stream.
+	pub fn from_reader(reader: R, encoding: Encoding, position: Position, metrics: M) -> Self {
+		Self::new(Decoder::new(reader, encoding, position, metrics.clone()), position, metrics)
+	}
+}
+
+/// Iterator over the characters of a [`SyncSourceBuffer`].
+///
+/// This iterator is created using the [`SyncSourceBuffer::iter`] method or the
+/// [`SyncSourceBuffer::iter_from`] method. When it reaches the end of the
+/// buffer, the buffer will start reading from the source stream until the
+/// stream itself return `None`.
+/// The buffer is locked for the time of each call to `next`.
+/// It can also iterate backward, reading the input stream until the end of
+/// the iteration first.
+pub struct SyncIter<'b, E, I: 'b + Iterator<Item = Result<char, E>>, M: Metrics> {
+	buffer: &'b SyncSourceBuffer<E, I, M>,
+	cursor: Cursor<E>,
+}
+
+impl<'b, E, I: 'b + Iterator<Item = Result<char, E>>, M: Metrics> SyncIter<'b, E, I, M> {
+	pub fn into_string(self) -> Result<String, BufferError<E>> {
+		let mut string = String::new();
+
+		for c in self {
+			string.push(c?);
+		}
+
+		Ok(string)
+	}
+}
+
+impl<'b, E, I: 'b + Iterator<Item = Result<char, E>>, M: Metrics> Iterator for SyncIter<'b, E, I, M> {
+	type Item = Result<char, BufferError<E>>;
+
+	fn next(&mut self) -> Option<Result<char, BufferError<E>>> {
+		self.buffer.lock().next_char(&mut self.cursor, &self.buffer.metrics)
+	}
+}
+
+impl<'b, E, I: 'b + Iterator<Item = Result<char, E>>, M: Metrics> DoubleEndedIterator for SyncIter<'b, E, I, M> {
+	fn next_back(&mut self) -> Option<Result<char, BufferError<E>>> {
+		self.buffer.lock().next_back_char(&mut self.cursor, &self.buffer.metrics)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::DefaultMetrics;
+
+	const TEXT: &str = "fn main() {\n\tprintln!(\"Hello\")\n}\n";
+
+	fn assert_sync<T: Send + Sync>(_: &T) {}
+
+	#[test]
+	fn shared_between_threads() {
+		let buffer = SyncSourceBuffer::new(TEXT.chars().map(Ok::<char, ()>), Position::default(), DefaultMetrics::default());
+		assert_sync(&buffer);
+
+		let lines: Vec<String> = std::thread::scope(|s| {
+			let threads: Vec<_> = (0..3).rev().map(|line| {
+				let buffer = &buffer;
+				s.spawn(move || {
+					let span = buffer.span_between(Position::new(line, 0), Position::new(line + 1, 0)).unwrap();
+					buffer.iter_span(span).into_string().unwrap()
+				})
+			}).collect();
+
+			threads.into_iter().map(|t| t.join().unwrap()).collect()
+		});
+
+		assert_eq!(lines, ["}\n", "\tprintln!(\"Hello\")\n", "fn main() {\n"]);
+		assert_eq!(buffer.at(Position::new(1, 8)), Ok(Some('p')));
+		assert_eq!(buffer.iter().into_string(), Ok(TEXT.to_string()));
+		assert_eq!(buffer.span(), Span::of_string(TEXT, &DefaultMetrics::default()));
+	}
+
+	#[test]
+	fn lines_shared_between_threads() {
+		let buffer = std::sync::Arc::new(SyncSourceBuffer::new(TEXT.chars().map(Ok::<char, ()>), Position::default(), DefaultMetrics::default()));
+
+		let threads: Vec<_> = (0..3).rev().map(|n| {
+			let buffer = buffer.clone();
+			std::thread::spawn(move || {
+				let line = buffer.line(n).unwrap().unwrap();
+				assert_eq!(buffer.slice_to_string(line.span()).as_deref(), Ok(line.as_str()));
+				line.content().to_string()
+			})
+		}).collect();
+
+		let lines: Vec<String> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+		assert_eq!(lines, ["}", "\tprintln!(\"Hello\")", "fn main() {"]);
+		assert_eq!(buffer.lines(1..).unwrap().len(), 2);
+
+		// the text is borrowed once the buffer is not shared anymore.
+		let mut buffer = std::sync::Arc::try_unwrap(buffer).ok().unwrap();
+		let span = buffer.line(1).unwrap().unwrap().span();
+		assert_eq!(buffer.slice(span), Ok("\tprintln!(\"Hello\")\n"));
+	}
+}
@@ -0,0 +1,535 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::{Metrics, Position, SourceError};
+
+/// Text encoding of a byte stream.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Encoding {
+	/// UTF-8.
+	Utf8,
+
+	/// UTF-16, little endian.
+	Utf16Le,
+
+	/// UTF-16, big endian.
+	Utf16Be,
+
+	/// ISO-8859-1 (Latin-1), where each byte is the code point of a
+	/// character.
+	Latin1,
+
+	/// Encoding detected from the byte order mark (BOM) of the stream (see
+	/// [`Encoding::detect`]), or UTF-8 if there is none.
+	Detect,
+}
+
+impl Encoding {
+	/// Detect the encoding of a byte stream starting with the given bytes
+	/// from its byte order mark (BOM).
+	///
+	/// Returns the detected encoding and the length of the BOM, if any.
+	#[must_use]
+	pub fn detect(bytes: &[u8]) -> Option<(Self, usize)> {
+		if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+			Some((Encoding::Utf8, 3))
+		} else if bytes.starts_with(&[0xff, 0xfe]) {
+			Some((Encoding::Utf16Le, 2))
+		} else if bytes.starts_with(&[0xfe, 0xff]) {
+			Some((Encoding::Utf16Be, 2))
+		} else {
+			None
+		}
+	}
+}
+
+impl fmt::Display for Encoding {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Encoding::Utf8 => write!(f, "UTF-8"),
+			Encoding::Utf16Le => write!(f, "UTF-16LE"),
+			Encoding::Utf16Be => write!(f, "UTF-16BE"),
+			Encoding::Latin1 => write!(f, "ISO-8859-1"),
+			Encoding::Detect => write!(f, "detected")
+		}
+	}
+}
+
+/// Decoding policy, defining what to do with invalid byte sequences.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum DecodePolicy {
+	/// Invalid byte sequences produce a [`DecodeError::InvalidSequence`] error.
+	///
+	/// Decoding can be resumed after the error.
+	#[default]
+	Strict,
+
+	/// Invalid byte sequences are replaced with the `U+FFFD` replacement
+	/// character.
+	Replace,
+}
+
+/// Decoding error.
+#[derive(Debug)]
+pub enum DecodeError {
+	/// The byte stream failed.
+	Io(io::Error),
+
+	/// Invalid byte sequence, located at the given position.
+	InvalidSequence(Position, Vec<u8>),
+}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			DecodeError::Io(e) => e.fmt(f),
+			DecodeError::InvalidSequence(_, bytes) => write!(f, "invalid byte sequence {:02x?}", bytes)
+		}
+	}
+}
+
+impl std::error::Error for DecodeError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			DecodeError::Io(e) => Some(e),
+			DecodeError::InvalidSequence(_, _) => None
+		}
+	}
+}
+
+impl From<io::Error> for DecodeError {
+	fn from(e: io::Error) -> Self { DecodeError::Io(e) }
+}
+
+impl From<DecodeError> for io::Error {
+	fn from(e: DecodeError) -> Self {
+		match e {
+			DecodeError::Io(e) => e,
+			DecodeError::InvalidSequence(position, _) => io::Error::new(io::ErrorKind::InvalidData, SourceError::new(position, e))
+		}
+	}
+}
+
+/// Decoder, turning a byte stream into a `char` stream.
+///
+/// It can be used as the input stream of a
+/// [`SourceBuffer`](crate::SourceBuffer), or of the
+/// [`Formatter`](crate::fmt::Formatter).
+/// The decoder keeps track of the position of the decoded characters to
+/// locate invalid byte sequences.
+///
+/// A byte order mark (BOM) at the begining of the stream is skipped if it
+/// matches the given encoding: the UTF-8 BOM in UTF-8, and either UTF-16 BOM
+/// in UTF-16, selecting the byte order. There is no BOM in Latin-1.
+/// Any BOM is accepted with [`Encoding::Detect`].
+///
+/// ```
+/// use source_span::{Decoder, DecodeError, DecodePolicy, Encoding, Position, DEFAULT_METRICS};
+///
+/// let bytes: &[u8] = &[0xef, 0xbb, 0xbf, b'a', b'\n', 0xff, b'b'];
+/// let mut chars = Decoder::new(bytes, Encoding::Utf8, Position::default(), DEFAULT_METRICS);
+///
+/// assert_eq!(chars.next().unwrap().unwrap(), 'a');
+/// assert_eq!(chars.next().unwrap().unwrap(), '\n');
+/// match chars.next().unwrap() {
+/// 	Err(DecodeError::InvalidSequence(pos, bytes)) => {
+/// 		assert_eq!(pos, Position::new(1, 0));
+/// 		assert_eq!(bytes, [0xff]);
+/// 	}
+/// 	_ => panic!("invalid sequence expected")
+/// }
+/// assert_eq!(chars.next().unwrap().unwrap(), 'b');
+///
+/// let chars = Decoder::new(bytes, Encoding::Utf8, Position::default(), DEFAULT_METRICS)
+/// 	.with_policy(DecodePolicy::Replace);
+/// assert_eq!(chars.collect::<Result<String, _>>().unwrap(), "a\n\u{fffd}b");
+/// ```
+pub struct Decoder<R, M> {
+	/// Byte stream.
+	reader: BufReader<R>,
+
+	/// Bytes read from the stream and put back, in reverse order.
+	pending: Vec<u8>,
+
+	/// Encoding, or `None` until the byte order mark is checked.
+	encoding: Option<Encoding>,
+
+	/// Encoding used if there is no byte order mark.
+	default_encoding: Encoding,
+
+	/// Decoding policy.
+	policy: DecodePolicy,
+
+	/// Position of the next character.
+	position: Position,
+
+	/// Previously decoded character.
+	previous: Option<char>,
+
+	/// Metrics used to compute the character positions.
+	metrics: M,
+}
+
+impl<R: Read, M: Metrics> Decoder<R, M> {
+	/// Create a new decoder reading the given byte stream, using the
+	/// [`DecodePolicy::Strict`] policy.
+	///
+	/// The first decoded character is located at the given position.
+	pub fn new(reader: R, encoding: Encoding, position: Position, metrics: M) -> Self {
+		Self {
+			reader: BufReader::new(reader),
+			pending: Vec::new(),
+			encoding: None,
+			default_encoding: encoding,
+			policy: DecodePolicy::Strict,
+			position,
+			previous: None,
+			metrics,
+		}
+	}
+
+	/// Set the decoding policy.
+	#[must_use]
+	pub fn with_policy(mut self, policy: DecodePolicy) -> Self {
+		self.policy = policy;
+		self
+	}
+
+	/// Get the decoding policy.
+	pub const fn policy(&self) -> DecodePolicy { self.policy }
+
+	/// Get the encoding of the byte stream.
+	///
+	/// This is the encoding given by the byte order mark, if any, once the
+	/// first character is decoded.
+	pub fn encoding(&self) -> Encoding { self.encoding.unwrap_or(self.default_encoding) }
+
+	/// Get the position of the next decoded character.
+	pub const fn position(&self) -> Position { self.position }
+
+	/// Read the next byte.
+	fn read_byte(&mut self) -> io::Result<Option<u8>> {
+		if let Some(byte) = self.pending.pop() {
+			return Ok(Some(byte))
+		}
+
+		loop {
+			match self.reader.fill_buf() {
+				Ok(buffer) => {
+					let byte = buffer.first().copied();
+					if byte.is_some() {
+						self.reader.consume(1)
+					}
+
+					return Ok(byte)
+				}
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+				Err(e) => return Err(e)
+			}
+		}
+	}
+
+	/// Put back the given bytes, to be read again.
+	fn unread(&mut self, bytes: &[u8]) { self.pending.extend(bytes.iter().rev()) }
+
+	/// Read the byte order mark, if any, and return the encoding to use.
+	fn read_bom(&mut self) -> io::Result<Encoding> {
+		if self.default_encoding == Encoding::Latin1 {
+			return Ok(Encoding::Latin1)
+		}
+
+		let mut bytes = Vec::with_capacity(3);
+		while bytes.len() < 3 {
+			match self.read_byte() {
+				Ok(Some(byte)) => bytes.push(byte),
+				Ok(None) => break,
+				Err(e) => {
+					self.unread(&bytes);
+					return Err(e)
+				}
+			}
+		}
+
+		// only the byte order marks of the given encoding are accepted.
+		let bom = Encoding::detect(&bytes).filter(|(encoding, _)| match self.default_encoding {
+			Encoding::Utf16Le | Encoding::Utf16Be => matches!(encoding, Encoding::Utf16Le | Encoding::Utf16Be),
+			default_encoding => default_encoding == Encoding::Detect || *encoding == default_encoding
+		});
+
+		match bom {
+			Some((encoding, len)) => {
+				self.unread(&bytes[len..]);
+				Ok(encoding)
+			}
+			None => {
+				self.unread(&bytes);
+				Ok(match self.default_encoding {
+					Encoding::Detect => Encoding::Utf8,
+					encoding => encoding
+				})
+			}
+		}
+	}
+
+	/// Decode the next UTF-8 character.
+	fn decode_utf8(&mut self, first: u8) -> io::Result<Result<char, Vec<u8>>> {
+		// length of the sequence and range of the second byte.
+		let (len, second) = match first {
+			0x00..=0x7f => return Ok(Ok(first as char)),
+			0xc2..=0xdf => (2, 0x80..=0xbf),
+			0xe0 => (3, 0xa0..=0xbf),
+			0xed => (3, 0x80..=0x9f),
+			0xe1..=0xef => (3, 0x80..=0xbf),
+			0xf0 => (4, 0x90..=0xbf),
+			0xf4 => (4, 0x80..=0x8f),
+			0xf1..=0xf3 => (4, 0x80..=0xbf),
+			_ => return Ok(Err(vec![first]))
+		};
+
+		let mut bytes = vec![first];
+		let mut code_point = u32::from(first) & (0x7f >> len);
+		for i in 1..len {
+			let range = if i == 1 { second.clone() } else { 0x80..=0xbf };
+			match self.read_byte()? {
+				Some(byte) if range.contains(&byte) => {
+					bytes.push(byte);
+					code_point = (code_point << 6) | u32::from(byte & 0x3f)
+				}
+				Some(byte) => {
+					// the byte may be the start of the next sequence.
+					self.unread(&[byte]);
+					return Ok(Err(bytes))
+				}
+				None => return Ok(Err(bytes))
+			}
+		}
+
+		Ok(std::char::from_u32(code_point).ok_or(bytes))
+	}
+
+	/// Read the next UTF-16 code unit.
+	///
+	/// Returns the bytes read if the stream ends before the end of the code
+	/// unit.
+	fn read_utf16_unit(&mut self, first: u8, little_endian: bool) -> io::Result<Result<u16, Vec<u8>>> {
+		match self.read_byte()? {
+			Some(second) => {
+				let bytes = [first, second];
+				Ok(Ok(if little_endian {
+					u16::from_le_bytes(bytes)
+				} else {
+					u16::from_be_bytes(bytes)
+				}))
+			}
+			None => Ok(Err(vec![first]))
+		}
+	}
+
+	/// Decode the next UTF-16 character.
+	fn decode_utf16(&mut self, first: u8, little_endian: bool) -> io::Result<Result<char, Vec<u8>>> {
+		let unit = match self.read_utf16_unit(first, little_endian)? {
+			Ok(unit) => unit,
+			Err(bytes) => return Ok(Err(bytes))
+		};
+
+		let unit_bytes = |unit: u16| if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() };
+
+		match unit {
+			0xd800..=0xdbff => {
+				let low = match self.read_byte()? {
+					Some(byte) => match self.read_utf16_unit(byte, little_endian)? {
+						Ok(low) => low,
+						Err(bytes) => {
+							self.unread(&bytes);
+							return Ok(Err(unit_bytes(unit).to_vec()))
+						}
+					},
+					None => return Ok(Err(unit_bytes(unit).to_vec()))
+				};
+
+				if (0xdc00..=0xdfff).contains(&low) {
+					let code_point = 0x10000 + ((u32::from(unit) - 0xd800) << 10) + (u32::from(low) - 0xdc00);
+					Ok(std::char::from_u32(code_point).ok_or_else(|| unit_bytes(unit).to_vec()))
+				} else {
+					// the code unit may be the start of the next character.
+					self.unread(&unit_bytes(low));
+					Ok(Err(unit_bytes(unit).to_vec()))
+				}
+			}
+			0xdc00..=0xdfff => Ok(Err(unit_bytes(unit).to_vec())),
+			unit => Ok(Ok(std::char::from_u32(u32::from(unit)).unwrap()))
+		}
+	}
+
+	/// Decode the next character.
+	///
+	/// Returns the invalid bytes if the next byte sequence is not valid.
+	fn decode(&mut self) -> io::Result<Option<Result<char, Vec<u8>>>> {
+		let encoding = match self.encoding {
+			Some(encoding) => encoding,
+			None => {
+				let encoding = self.read_bom()?;
+				self.encoding = Some(encoding);
+				encoding
+			}
+		};
+
+		let first = match self.read_byte()? {
+			Some(byte) => byte,
+			None => return Ok(None)
+		};
+
+		Ok(Some(match encoding {
+			Encoding::Utf8 | Encoding::Detect => self.decode_utf8(first)?,
+			Encoding::Utf16Le => self.decode_utf16(first, true)?,
+			Encoding::Utf16Be => self.decode_utf16(first, false)?,
+			Encoding::Latin1 => Ok(char::from(first))
+		}))
+	}
+}
+
+impl<R: Read, M: Metrics> Iterator for Decoder<R, M> {
+	type Item = Result<char, DecodeError>;
+
+	fn next(&mut self) -> Option<Result<char, DecodeError>> {
+		let c = match self.decode() {
+			Ok(Some(Ok(c))) => c,
+			Ok(Some(Err(bytes))) => match self.policy {
+				DecodePolicy::Strict => return Some(Err(DecodeError::InvalidSequence(self.position, bytes))),
+				DecodePolicy::Replace => std::char::REPLACEMENT_CHARACTER
+			},
+			Ok(None) => return None,
+			Err(e) => return Some(Err(DecodeError::Io(e)))
+		};
+
+		self.position = self.position.next_after(self.previous, c, &self.metrics);
+		self.previous = Some(c);
+		Some(Ok(c))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::DEFAULT_METRICS;
+
+	fn decode(bytes: &[u8], encoding: Encoding) -> Vec<Result<char, (Position, Vec<u8>)>> {
+		Decoder::new(bytes, encoding, Position::default(), DEFAULT_METRICS)
+			.map(|c| c.map_err(|e| match e {
+				DecodeError::InvalidSequence(pos, bytes) => (pos, bytes),
+				DecodeError::Io(e) => panic!("{}", e)
+			}))
+			.collect()
+	}
+
+	fn replace(bytes: &[u8], encoding: Encoding) -> String {
+		Decoder::new(bytes, encoding, Position::default(), DEFAULT_METRICS)
+			.with_policy(DecodePolicy::Replace)
+			.collect::<Result<String, _>>()
+			.unwrap()
+	}
+
+	#[test]
+	fn utf8() {
+		let text = "a\u{0}é\n字\u{ffff}🦀\u{10ffff}";
+		assert_eq!(replace(text.as_bytes(), Encoding::Utf8), text);
+		assert_eq!(replace(&[0xef, 0xbb, 0xbf, b'a'], Encoding::Utf8), "a");
+		assert_eq!(replace(&[0xef, 0xbb], Encoding::Utf8), "\u{fffd}");
+
+		let p = Position::new;
+		assert_eq!(decode(&[b'a', 0xc3, b'b', b'\n', 0xe5, 0xad], Encoding::Utf8), [
+			Ok('a'),
+			Err((p(0, 1), vec![0xc3])),
+			Ok('b'),
+			Ok('\n'),
+			Err((p(1, 0), vec![0xe5, 0xad]))
+		]);
+
+		// overlong encodings, surrogates and code points after `U+10FFFF`.
+		for bytes in &[&[0xc0, 0x80][..], &[0xe0, 0x80, 0x80], &[0xed, 0xa0, 0x80], &[0xf4, 0x90, 0x80, 0x80], &[0xf5]] {
+			assert!(replace(bytes, Encoding::Utf8).chars().all(|c| c == '\u{fffd}'), "{:02x?}", bytes)
+		}
+	}
+
+	#[test]
+	fn utf16() {
+		let text = "a\u{0}é\n字\u{ffff}🦀\u{10ffff}";
+		let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+		let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+		assert_eq!(replace(&le, Encoding::Utf16Le), text);
+		assert_eq!(replace(&be, Encoding::Utf16Be), text);
+
+		// a UTF-16 byte order mark selects the byte order.
+		assert_eq!(replace(&[0xfe, 0xff, 0x00, b'a'], Encoding::Utf16Le), "a");
+		assert_eq!(replace(&[0xff, 0xfe, b'a', 0x00], Encoding::Utf16Be), "a");
+		assert_eq!(replace(&[0xef, 0xbb, 0xbf, b'a'], Encoding::Utf16Le), "\u{bbef}\u{61bf}");
+
+		let p = Position::new;
+		assert_eq!(decode(&[0x00, 0xd8, b'a', 0x00, 0x00, 0xdc, b'b'], Encoding::Utf16Le), [
+			Err((p(0, 0), vec![0x00, 0xd8])),
+			Ok('a'),
+			Err((p(0, 1), vec![0x00, 0xdc])),
+			Err((p(0, 1), vec![b'b']))
+		]);
+	}
+
+	#[test]
+	fn detect() {
+		assert_eq!(replace(&[0xef, 0xbb, 0xbf, b'a'], Encoding::Detect), "a");
+		assert_eq!(replace(&[0xff, 0xfe, b'a', 0x00], Encoding::Detect), "a");
+		assert_eq!(replace(&[0xfe, 0xff, 0x00, b'a'], Encoding::Detect), "a");
+		assert_eq!(replace("aé".as_bytes(), Encoding::Detect), "aé");
+
+		let mut decoder = Decoder::new(&[0xfe, 0xff, 0x00, b'a'][..], Encoding::Detect, Position::default(), DEFAULT_METRICS);
+		assert_eq!(decoder.encoding(), Encoding::Detect);
+		assert!(decoder.next().is_some());
+		assert_eq!(decoder.encoding(), Encoding::Utf16Be);
+
+		// other encodings only accept their own byte order marks.
+		assert_eq!(replace(&[0xff, 0xfe, b'a'], Encoding::Utf8), "\u{fffd}\u{fffd}a");
+		assert_eq!(replace(&[0xef, 0xbb, 0xbf, b'a'], Encoding::Latin1), "\u{ef}\u{bb}\u{bf}a");
+	}
+
+	#[test]
+	fn replace_as_std() {
+		let bytes = [0x00, 0x41, 0x7f, 0x80, 0xbf, 0xc0, 0xc2, 0xdf, 0xe0, 0xed, 0xef, 0xf0, 0xf4, 0xf5, 0xff];
+		for a in 0..=255 {
+			for b in 0..=255 {
+				let seq = [a, b];
+				assert_eq!(replace(&seq, Encoding::Utf8), String::from_utf8_lossy(&seq), "{:02x?}", seq);
+			}
+		}
+
+		for a in &bytes {
+			for b in &bytes {
+				for c in &bytes {
+					for d in &bytes {
+						let seq = [*a, *b, *c, *d];
+						assert_eq!(replace(&seq, Encoding::Utf8), String::from_utf8_lossy(&seq), "{:02x?}", seq);
+					}
+				}
+			}
+		}
+
+		let units = [0x0041, 0xd7ff, 0xd800, 0xdbff, 0xdc00, 0xdfff, 0xe000, 0xfeff];
+		for a in &units {
+			for b in &units {
+				for c in &units {
+					let seq = [*a, *b, *c];
+					let expected: String = std::char::decode_utf16(seq.iter().copied())
+						.map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER))
+						.collect();
+					let bytes: Vec<u8> = seq.iter().copied().flat_map(u16::to_be_bytes).collect();
+					if Encoding::detect(&bytes).is_none() {
+						assert_eq!(replace(&bytes, Encoding::Utf16Be), expected, "{:04x?}", seq);
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn latin1() {
+		assert_eq!(replace(&[b'a', 0xe9, 0xff, 0x80], Encoding::Latin1), "aéÿ\u{80}");
+	}
+}
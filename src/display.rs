@@ -0,0 +1,291 @@
+use std::fmt;
+use crate::{
+	Position,
+	Span
+};
+
+/// Location format style.
+///
+/// Defines how a [`Position`] or [`Span`] is formatted by a [`PositionFormat`].
+/// In the following, `L` and `C` stand for line and column numbers, and `file`
+/// for the optional file name of the format, followed by `:` when defined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum PositionStyle {
+	/// Human readable format.
+	///
+	/// Positions are formatted as `line L column C`,
+	/// and spans as `from line L column C to line L column C`.
+	/// The file name is appended as `in file`.
+	#[default]
+	Text,
+
+	/// `file:L:C` format.
+	///
+	/// Spans are formatted as `file:L:C-L:C`.
+	Colon,
+
+	/// [GNU error format](https://www.gnu.org/prep/standards/html_node/Errors.html).
+	///
+	/// Positions are formatted as `file:L:C`, and spans as `file:L.C-L.C`, or
+	/// `file:L.C-C` when the span is on a single line.
+	Gnu,
+
+	/// Emacs error format.
+	///
+	/// This is the same as the [`Gnu`](PositionStyle::Gnu) format, which is
+	/// recognized by the Emacs compilation mode (including ranges).
+	Emacs,
+
+	/// Vim error format.
+	///
+	/// Positions and spans are formatted as `file:L:C`, recognized by the
+	/// default Vim `errorformat`. Since Vim does not support ranges, spans
+	/// are formatted using their start position only.
+	Vim
+}
+
+/// Location format.
+///
+/// Defines how a [`Position`] or [`Span`] is displayed using
+/// [`Position::display_with`] or [`Span::display_with`].
+///
+/// The end of a span is given by its [`last`](Span::last) position, included
+/// in the span.
+/// Lines and columns equal to `usize::MAX` are formatted as `[end]`.
+///
+/// ```
+/// use source_span::{Position, PositionFormat, PositionStyle, Span};
+///
+/// let span = Span::new(Position::new(1, 2), Position::new(3, 4), Position::new(3, 5));
+///
+/// let format = PositionFormat::new(1, PositionStyle::Gnu).with_file("main.rs");
+/// assert_eq!(span.display_with(format).to_string(), "main.rs:2.3-4.5");
+///
+/// let format = PositionFormat::new(0, PositionStyle::Colon);
+/// assert_eq!(span.display_with(format).to_string(), "1:2-3:4");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PositionFormat<'a> {
+	/// Number of the first line and column.
+	///
+	/// This is `1` by default.
+	pub base: usize,
+
+	/// Format style.
+	pub style: PositionStyle,
+
+	/// Optional file name.
+	pub file: Option<&'a str>
+}
+
+impl<'a> PositionFormat<'a> {
+	/// Create a new location format with the given base and style, without
+	/// file name.
+	#[must_use]
+	pub const fn new(base: usize, style: PositionStyle) -> Self {
+		Self {
+			base,
+			style,
+			file: None
+		}
+	}
+
+	/// Set the file name.
+	#[must_use]
+	pub const fn with_file(self, file: &'a str) -> Self {
+		Self {
+			file: Some(file),
+			..self
+		}
+	}
+
+	fn fmt_index(&self, f: &mut fmt::Formatter, index: usize) -> fmt::Result {
+		if index == usize::MAX {
+			write!(f, "[end]")
+		} else {
+			write!(f, "{}", index.saturating_add(self.base))
+		}
+	}
+
+	fn fmt_file(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.file {
+			Some(file) => write!(f, "{}:", file),
+			None => Ok(())
+		}
+	}
+
+	fn fmt_position(&self, f: &mut fmt::Formatter, pos: Position, sep: &str) -> fmt::Result {
+		self.fmt_index(f, pos.line)?;
+		write!(f, "{}", sep)?;
+		self.fmt_index(f, pos.column)
+	}
+
+	fn fmt_text_position(&self, f: &mut fmt::Formatter, pos: Position) -> fmt::Result {
+		write!(f, "line ")?;
+		self.fmt_index(f, pos.line)?;
+		write!(f, " column ")?;
+		self.fmt_index(f, pos.column)
+	}
+
+	fn fmt_text_file(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.file {
+			Some(file) => write!(f, " in {}", file),
+			None => Ok(())
+		}
+	}
+
+	fn fmt_span(&self, f: &mut fmt::Formatter, span: Span) -> fmt::Result {
+		let start = span.start();
+		let last = span.last();
+
+		if start == last {
+			return self.fmt_location(f, start)
+		}
+
+		match self.style {
+			PositionStyle::Text => {
+				write!(f, "from ")?;
+				self.fmt_text_position(f, start)?;
+				write!(f, " to ")?;
+				self.fmt_text_position(f, last)?;
+				self.fmt_text_file(f)
+			},
+			PositionStyle::Colon => {
+				self.fmt_file(f)?;
+				self.fmt_position(f, start, ":")?;
+				write!(f, "-")?;
+				self.fmt_position(f, last, ":")
+			},
+			PositionStyle::Gnu | PositionStyle::Emacs => {
+				self.fmt_file(f)?;
+				self.fmt_position(f, start, ".")?;
+				write!(f, "-")?;
+				if start.line == last.line {
+					self.fmt_index(f, last.column)
+				} else {
+					self.fmt_position(f, last, ".")
+				}
+			},
+			PositionStyle::Vim => self.fmt_location(f, start)
+		}
+	}
+
+	fn fmt_location(&self, f: &mut fmt::Formatter, pos: Position) -> fmt::Result {
+		match self.style {
+			PositionStyle::Text => {
+				self.fmt_text_position(f, pos)?;
+				self.fmt_text_file(f)
+			},
+			_ => {
+				self.fmt_file(f)?;
+				self.fmt_position(f, pos, ":")
+			}
+		}
+	}
+}
+
+impl<'a> Default for PositionFormat<'a> {
+	fn default() -> Self {
+		Self::new(1, PositionStyle::default())
+	}
+}
+
+/// Position display adapter.
+///
+/// Displays a [`Position`] using a given [`PositionFormat`].
+/// It is created using [`Position::display_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayPosition<'a> {
+	position: Position,
+	format: PositionFormat<'a>
+}
+
+impl<'a> DisplayPosition<'a> {
+	pub(crate) const fn new(position: Position, format: PositionFormat<'a>) -> Self {
+		Self {
+			position,
+			format
+		}
+	}
+}
+
+impl<'a> fmt::Display for DisplayPosition<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.format.fmt_location(f, self.position)
+	}
+}
+
+/// Span display adapter.
+///
+/// Displays a [`Span`] using a given [`PositionFormat`].
+/// It is created using [`Span::display_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct DisplaySpan<'a> {
+	span: Span,
+	format: PositionFormat<'a>
+}
+
+impl<'a> DisplaySpan<'a> {
+	pub(crate) const fn new(span: Span, format: PositionFormat<'a>) -> Self {
+		Self {
+			span,
+			format
+		}
+	}
+}
+
+impl<'a> fmt::Display for DisplaySpan<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.format.fmt_span(f, self.span)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn span(a: (usize, usize), b: (usize, usize), c: (usize, usize)) -> Span {
+		Span::new(Position::new(a.0, a.1), Position::new(b.0, b.1), Position::new(c.0, c.1))
+	}
+
+	#[test]
+	fn display_position() {
+		let pos = Position::new(2, 3);
+		let format = |base, style| PositionFormat::new(base, style).with_file("main.rs");
+
+		assert_eq!(pos.display_with(PositionFormat::default()).to_string(), pos.to_string());
+		assert_eq!(pos.display_with(format(0, PositionStyle::Text)).to_string(), "line 2 column 3 in main.rs");
+		assert_eq!(pos.display_with(format(1, PositionStyle::Colon)).to_string(), "main.rs:3:4");
+		assert_eq!(pos.display_with(format(1, PositionStyle::Gnu)).to_string(), "main.rs:3:4");
+		assert_eq!(pos.display_with(format(0, PositionStyle::Vim)).to_string(), "main.rs:2:3");
+		assert_eq!(Position::end().display_with(PositionFormat::new(1, PositionStyle::Colon)).to_string(), "[end]:[end]");
+	}
+
+	#[test]
+	fn display_span() {
+		let multi_line = span((0, 4), (2, 0), (2, 1));
+		let single_line = span((1, 4), (1, 7), (1, 8));
+		let single_char = span((1, 4), (1, 4), (1, 5));
+		let format = |style| PositionFormat::new(1, style).with_file("main.rs");
+
+		assert_eq!(multi_line.display_with(PositionFormat::default()).to_string(), "from line 1 column 5 to line 3 column 1");
+		assert_eq!(multi_line.display_with(format(PositionStyle::Colon)).to_string(), "main.rs:1:5-3:1");
+		assert_eq!(multi_line.display_with(format(PositionStyle::Gnu)).to_string(), "main.rs:1.5-3.1");
+		assert_eq!(multi_line.display_with(format(PositionStyle::Emacs)).to_string(), "main.rs:1.5-3.1");
+		assert_eq!(multi_line.display_with(format(PositionStyle::Vim)).to_string(), "main.rs:1:5");
+		assert_eq!(single_line.display_with(format(PositionStyle::Gnu)).to_string(), "main.rs:2.5-8");
+		assert_eq!(single_char.display_with(format(PositionStyle::Gnu)).to_string(), "main.rs:2:5");
+		assert_eq!(single_char.display_with(format(PositionStyle::Text)).to_string(), "line 2 column 5 in main.rs");
+	}
+
+	#[test]
+	fn display_large_index() {
+		// the last position of a span ending with `[end]`.
+		let pos = Position::new(usize::MAX - 1, usize::MAX - 1);
+		let format = PositionFormat::new(2, PositionStyle::Colon);
+		assert_eq!(pos.display_with(format).to_string(), format!("{0}:{0}", usize::MAX));
+
+		let span = Span::new(Position::new(0, 0), Position::new(0, usize::MAX - 1), Position::new(0, usize::MAX));
+		assert_eq!(span.display_with(format).to_string(), format!("2:2-2:{}", usize::MAX));
+	}
+}
@@ -61,10 +61,12 @@
 //! ```
 
 use crate::{Metrics, Position, Span};
+use std::borrow::Cow;
 use std::fmt;
 
 /// Colors used to render the text.
-#[cfg(feature = "colors")]
+///
+/// Colors are only rendered if the `colors` feature is enabled.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Color {
 	Red,
@@ -100,9 +102,6 @@ impl termion::color::Color for Color {
 	}
 }
 
-#[cfg(not(feature = "colors"))]
-pub type Color = ();
-
 /// Highlight format description.
 ///
 /// Specifies how the highlight should be rendered:
@@ -129,10 +128,12 @@ pub type Color = ();
 ///
 /// ## Colors
 ///
-/// If the `colors` feature is enabled, it is also possible to set a color to
-/// draw the lines. This will also make the highlights more bright (or bold),
-/// along with the line numbers.
-#[derive(Clone, Copy)]
+/// Each style has a color used to draw the lines.
+/// If the `colors` feature is enabled, this will also make the highlights more
+/// bright (or bold), along with the line numbers.
+/// Without the `colors` feature, or when rendering with
+/// [`Formatted::plain`], the color is ignored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Style {
 	/// Red curvy underline.
 	Error,
@@ -148,28 +149,18 @@ pub enum Style {
 
 	/// Custom highlight format.
 	///
-	/// Specifies the underline character, the boundary marker and the color (if
-	/// the `colors` feature is enabled) used to render the highlight.
+	/// Specifies the underline character, the boundary marker and the color
+	/// used to render the highlight.
 	Custom(char, char, Color),
 }
 
 impl Style {
-	/// Create a new custom highlight style.
-	///
-	/// The `line` character is user to draw the line under the span elements.
-	/// The `marker` character is used to point to the first and last elements
-	/// of the span when relevant.
-	#[must_use]
-	#[cfg(not(feature = "colors"))]
-	pub const fn new(underline: char, marker: char) -> Self { Self::Custom(underline, marker, ()) }
-
 	/// Create a new custom highlight style.
 	///
 	/// The `line` character is user to draw the line under the highlighted
 	/// sections. The `marker` character is used to point to the first and last
 	/// elements of the section when relevant.
 	#[must_use]
-	#[cfg(feature = "colors")]
 	pub const fn new(line: char, marker: char, color: Color) -> Self {
 		Self::Custom(line, marker, color)
 	}
@@ -195,22 +186,29 @@ impl Style {
 		}
 	}
 
-	/// Get the color used to draw the highlight.
+	/// The name of the style, used in the [`Mode::Short`] output mode.
+	///
+	/// Custom styles have no name.
 	#[must_use]
-	pub fn color(&self) -> Color {
-		#[cfg(not(feature = "colors"))]
-		{
-			()
+	pub const fn name(&self) -> Option<&'static str> {
+		match self {
+			Self::Error => Some("error"),
+			Self::Warning => Some("warning"),
+			Self::Note => Some("note"),
+			Self::Help => Some("help"),
+			Self::Custom(_, _, _) => None,
 		}
-		#[cfg(feature = "colors")]
-		{
-			match self {
-				Self::Error => Color::Red,
-				Self::Warning => Color::Yellow,
-				Self::Note => Color::Blue,
-				Self::Help => Color::Green,
-				Self::Custom(_, _, color) => *color,
-			}
+	}
+
+	/// Get the color used to draw the highlight.
+	#[must_use]
+	pub const fn color(&self) -> Color {
+		match self {
+			Self::Error => Color::Red,
+			Self::Warning => Color::Yellow,
+			Self::Note => Color::Blue,
+			Self::Help => Color::Green,
+			Self::Custom(_, _, color) => *color,
 		}
 	}
 }
@@ -283,6 +281,23 @@ impl Style {
 /// 3 | | }
 ///   | |_^ this is a pair of braces
 /// ```
+///
+/// # Empty highlights
+///
+/// A highlight with an empty span points between two characters, where some
+/// text is expected for instance. It is rendered with a single marker placed
+/// under the character following the position (or after the end of the line).
+///
+/// ```txt
+/// 1 | let x = 1
+///   |          ^ expected `;`
+/// 2 | let y = 2;
+/// ```
+///
+/// Highlights located after the end of the rendered span are moved to the end
+/// of the span, so that errors pointing past the end of the input are still
+/// visible.
+#[derive(Clone)]
 pub struct Highlight {
 	span: Span,
 	label: Option<String>,
@@ -366,6 +381,70 @@ impl Highlight {
 	}
 }
 
+/// Output mode of the [`Formatter`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Mode {
+	/// Full ASCII-art rendering of the source text (this is the default).
+	///
+	/// ```txt
+	/// 1 |   fn main() {
+	///   |  ___________^
+	/// 2 | |     println!("Hello World!")
+	///   | |              ^^^^^^^^^^^^^^ a string
+	/// 3 | | }
+	///   | |_^ a block
+	/// ```
+	#[default]
+	Full,
+
+	/// One line per highlight, in the style of `gcc` or `clang`.
+	///
+	/// ```txt
+	/// main.rs:1:11: error: a block
+	/// main.rs:2:14: error: a string
+	/// ```
+	///
+	/// The source name can be set using
+	/// [`Formatter::set_source_name`]. The source text is not rendered.
+	Short,
+
+	/// Each highlighted line followed by a single line of markers, without
+	/// connecting lines.
+	///
+	/// ```txt
+	/// 1 | fn main() {
+	///   |           ^
+	/// 2 |     println!("Hello World!")
+	///   |              ^^^^^^^^^^^^^^ a string
+	/// 3 | }
+	///   | ^ a block
+	/// ```
+	///
+	/// Labels are rendered on the marker line of the last line of their
+	/// highlight.
+	Compact,
+}
+
+/// Rendering of tabulations in the source text.
+///
+/// In any case, a tabulation is rendered up to the next tab stop given by the
+/// [`Metrics`] used to render the text, so that highlights stay aligned with
+/// the text. To render tabulations with a different width (such as the 4
+/// columns used by `rustc`), the highlighted spans must be computed with the
+/// same metrics as the rendering, for instance
+/// [`DefaultMetrics::with_tab_stop`](crate::DefaultMetrics::with_tab_stop).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TabStyle {
+	/// Tabulations are expanded to spaces (this is the default).
+	///
+	/// This is the rendering of tabulations by previous versions.
+	#[default]
+	Spaces,
+
+	/// Tabulations are expanded to a visible `→` glyph followed by spaces.
+	Arrow,
+}
+
 /// Text formatter with span highlights.
 ///
 /// This allows you to format a given input `char` stream with highlights and
@@ -388,6 +467,10 @@ pub struct Formatter {
 	show_line_numbers: bool,
 	use_line_begining_shortcut: bool,
 	viewbox: Option<usize>,
+	mode: Mode,
+	source_name: Option<String>,
+	tab_style: TabStyle,
+	show_invisible_characters: bool,
 }
 
 impl Formatter {
@@ -415,6 +498,10 @@ impl Formatter {
 			viewbox: Some(2),
 			show_line_numbers: true,
 			use_line_begining_shortcut: true,
+			mode: Mode::Full,
+			source_name: None,
+			tab_style: TabStyle::Spaces,
+			show_invisible_characters: false,
 		}
 	}
 
@@ -483,10 +570,41 @@ impl Formatter {
 	/// function. In this case, all the lines will be visible.
 	pub fn set_viewbox(&mut self, viewbox: Option<usize>) { self.viewbox = viewbox }
 
+	/// Set the output mode (default is [`Mode::Full`]).
+	pub fn set_mode(&mut self, mode: Mode) { self.mode = mode }
+
+	/// Set the name of the source (a file path for instance).
+	///
+	/// It is used as a prefix of each line rendered in the [`Mode::Short`]
+	/// output mode.
+	pub fn set_source_name(&mut self, name: Option<String>) { self.source_name = name }
+
+	/// Set how tabulations are rendered (default is [`TabStyle::Spaces`]).
+	pub fn set_tab_style(&mut self, tab_style: TabStyle) { self.tab_style = tab_style }
+
+	/// By default, invisible and control characters are rendered as is.
+	/// Making them visible will render them with their
+	/// [`visible_placeholder`](`crate::visible_placeholder`) instead
+	/// (`␍`, `␀`, `<U+200B>`, etc.):
+	///
+	/// ```text
+	/// 1 | let x = 1;␍
+	///   |           ^ stray carriage return
+	/// ```
+	///
+	/// The placeholder is rendered in the columns given to the character by
+	/// the metrics used to render the text, so that highlights stay aligned.
+	/// If there is not enough room for the placeholder, the columns are filled
+	/// with `�`. Use [`VisibleMetrics`](`crate::VisibleMetrics`) to compute
+	/// the spans and render the text to make room for the placeholders.
+	pub fn set_invisible_characters_visible(&mut self, visible: bool) {
+		self.show_invisible_characters = visible
+	}
+
 	/// Add a span highlight.
 	pub fn add(&mut self, span: Span, label: Option<String>, style: Style) {
 		self.highlights.push(Highlight { span, label, style });
-		self.highlights.sort_by(|a, b| a.span.cmp(&b.span));
+		self.highlights.sort_by(|a, b| a.span.containment_cmp(&b.span));
 	}
 
 	/// Returns the smallest span including every highlights.
@@ -577,26 +695,22 @@ impl Char {
 	}
 
 	const fn is_free(&self) -> bool {
-		match self {
-			Self::Empty => true,
-			_ => false,
-		}
+		matches!(self, Self::Empty)
+	}
+
+	#[allow(clippy::trivially_copy_pass_by_ref)]
+	const fn is_free_or_vertical(&self) -> bool {
+		matches!(self, Self::Empty | Self::SpanVertical(_))
 	}
 
 	#[allow(clippy::trivially_copy_pass_by_ref)]
 	const fn is_span_horizontal(&self) -> bool {
-		match self {
-			Self::SpanHorizontal(_) => true,
-			_ => false,
-		}
+		matches!(self, Self::SpanHorizontal(_))
 	}
 
 	#[allow(clippy::trivially_copy_pass_by_ref)]
 	const fn is_span_margin(&self) -> bool {
-		match self {
-			Self::SpanMargin(_) => true,
-			_ => false,
-		}
+		matches!(self, Self::SpanMargin(_))
 	}
 }
 
@@ -604,6 +718,17 @@ impl From<char> for Char {
 	fn from(c: char) -> Self { Self::Text(c) }
 }
 
+/// Maximum width and height of a [`CharMap`].
+///
+/// Characters drawn outside of these bounds are dropped, so that positions
+/// far from the rendered text cannot exhaust the memory.
+const CHARMAP_MAX_SIZE: usize = 1 << 16;
+
+/// Maximum area (width times height) of a [`CharMap`].
+///
+/// Characters that would grow the char map past this area are dropped.
+const CHARMAP_MAX_AREA: usize = 1 << 22;
+
 /// A 2D character map.
 struct CharMap {
 	data: Vec<Char>,
@@ -627,11 +752,27 @@ impl CharMap {
 			height: 0,
 		};
 
+		// the map is allocated at once, instead of growing with each line.
+		let (mut width, mut height) = (0, 0);
+		let mut pos = Position::new(0, 0);
+		for c in text.chars() {
+			if c != '\n' && c != '\t' {
+				width = std::cmp::max(width, pos.column.saturating_add(1));
+				height = pos.line.saturating_add(1);
+			}
+
+			pos.shift(c, metrics)
+		}
+
+		let width = std::cmp::min(width, CHARMAP_MAX_SIZE);
+		map.reserve(width, std::cmp::min(height, CHARMAP_MAX_AREA / std::cmp::max(width, 1)));
+
 		let mut pos = Position::new(0, 0);
 		for c in text.chars() {
 			match c {
 				'\n' | '\t' => (),
-				_ => map.set(pos.column, pos.line, Char::Label(c, color)),
+				_ if pos.column < map.width && pos.line < map.height => map.set(pos.column, pos.line, Char::Label(c, color)),
+				_ => (),
 			}
 
 			pos.shift(c, metrics)
@@ -698,10 +839,11 @@ impl CharMap {
 	}
 
 	fn reserve(&mut self, width: usize, height: usize) {
-		self.resize(
-			std::cmp::max(width, self.width),
-			std::cmp::max(height, self.height),
-		)
+		let width = std::cmp::max(std::cmp::min(width, CHARMAP_MAX_SIZE), self.width);
+		let height = std::cmp::max(std::cmp::min(height, CHARMAP_MAX_SIZE), self.height);
+		if width * height <= CHARMAP_MAX_AREA {
+			self.resize(width, height)
+		}
 	}
 
 	fn get(&self, x: usize, y: usize) -> Char {
@@ -713,13 +855,17 @@ impl CharMap {
 	}
 
 	fn set(&mut self, x: usize, y: usize, c: Char) {
-		self.reserve(x + 1, y + 1);
-		self.data[x + y * self.width] = c;
+		if x < CHARMAP_MAX_SIZE && y < CHARMAP_MAX_SIZE {
+			self.reserve(x + 1, y + 1);
+			if x < self.width && y < self.height {
+				self.data[x + y * self.width] = c;
+			}
+		}
 	}
 
 	fn draw_marker(&mut self, style: &Style, y: usize, x: usize) {
 		let mut head = false;
-		for j in 1..=y {
+		for j in 1..=std::cmp::min(y, CHARMAP_MAX_SIZE) {
 			let previous_c = self.get(x, j);
 			if previous_c.is_free() || previous_c.is_span_horizontal() {
 				let c = if head {
@@ -735,7 +881,8 @@ impl CharMap {
 	}
 
 	fn draw_open_line(&mut self, style: &Style, y: usize, start: usize, end: usize) {
-		self.reserve(end + 1, y + 1);
+		let end = std::cmp::min(end, CHARMAP_MAX_SIZE);
+		self.reserve(end + 1, y.saturating_add(1));
 		for x in start..=end {
 			if x == end {
 				self.draw_marker(style, y, x)
@@ -748,26 +895,30 @@ impl CharMap {
 	}
 
 	fn draw_closed_line(&mut self, style: &Style, y: usize, start: usize, end: usize) {
-		self.reserve(end + 1, y + 1);
+		let end = std::cmp::min(end, CHARMAP_MAX_SIZE);
+		self.reserve(end + 1, y.saturating_add(1));
 		for x in start..=end {
 			if x == start || x == end {
 				self.draw_marker(style, y, x)
 			} else {
-				let c = if y == 1 {
-					Char::SpanUnderline(style.line(), style.color())
+				if y == 1 {
+					// on the marker line, the markers of enclosed spans are kept.
+					if self.get(x, y).is_free() {
+						self.set(x, y, Char::SpanUnderline(style.line(), style.color()))
+					}
 				} else {
-					Char::SpanHorizontal(style.color())
-				};
-
-				self.set(x, y, c)
+					self.set(x, y, Char::SpanHorizontal(style.color()))
+				}
 			}
 		}
 	}
 
 	/// Checks if the given rectangle is free in the char map.
 	fn is_rect_free(&self, offset_x: usize, offset_y: usize, width: usize, height: usize) -> bool {
-		for y in offset_y..(offset_y + height) {
-			for x in offset_x..(offset_x + width) {
+		let end_y = std::cmp::min(offset_y.saturating_add(height), CHARMAP_MAX_SIZE);
+		let end_x = std::cmp::min(offset_x.saturating_add(width), CHARMAP_MAX_SIZE);
+		for y in offset_y..end_y {
+			for x in offset_x..end_x {
 				if !self.get(x, y).is_free() {
 					return false;
 				}
@@ -778,10 +929,13 @@ impl CharMap {
 	}
 
 	fn draw_charmap(&mut self, offset_x: usize, offset_y: usize, map: &CharMap) {
-		self.reserve(offset_x + map.width, offset_y + map.height);
-		for y in 0..map.height {
+		// only the rows fitting in the maximum area are drawn.
+		let width = std::cmp::max(std::cmp::min(offset_x.saturating_add(map.width), CHARMAP_MAX_SIZE), self.width);
+		let height = std::cmp::min(map.height, (CHARMAP_MAX_AREA / std::cmp::max(width, 1)).saturating_sub(offset_y));
+		self.reserve(offset_x.saturating_add(map.width), offset_y.saturating_add(height));
+		for y in 0..height {
 			for x in 0..map.width {
-				self.set(offset_x + x, offset_y + y, map.get(x, y))
+				self.set(offset_x.saturating_add(x), offset_y.saturating_add(y), map.get(x, y))
 			}
 		}
 	}
@@ -789,6 +943,7 @@ impl CharMap {
 	fn draw_charmap_if_free(&mut self, offset_x: usize, offset_y: usize, map: &CharMap) -> bool {
 		let mut dx = 0;
 		let mut dy = 0;
+		let mut padding_y = 1;
 
 		if offset_x > 0 {
 			dx = 1;
@@ -796,13 +951,17 @@ impl CharMap {
 
 		if offset_y > 1 {
 			dy = 1;
+		} else {
+			// labels placed on the marker line do not need a free line below them
+			// since they have no connector.
+			padding_y = 0;
 		}
 
 		if self.is_rect_free(
 			offset_x - dx,
 			offset_y - dy,
 			map.width + dx + 1,
-			map.height + dy + 1,
+			map.height + dy + padding_y,
 		) {
 			self.draw_charmap(offset_x, offset_y, map);
 			true
@@ -812,6 +971,20 @@ impl CharMap {
 	}
 }
 
+impl CharMap {
+	/// Format the char map without colors.
+	fn fmt_plain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for y in 0..self.height {
+			for x in 0..self.width {
+				write!(f, "{}", self.get(x, y).unwrap())?;
+			}
+			writeln!(f)?;
+		}
+
+		Ok(())
+	}
+}
+
 impl fmt::Display for CharMap {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		#[cfg(feature = "colors")]
@@ -833,7 +1006,7 @@ impl fmt::Display for CharMap {
 				}
 				c.unwrap().fmt(f)?;
 			}
-			write!(f, "\n")?;
+			writeln!(f)?;
 		}
 
 		#[cfg(feature = "colors")]
@@ -849,6 +1022,60 @@ impl fmt::Display for CharMap {
 /// It implements [`Display`](`fmt::Display`) and can hence be printted with a simple `printf!`.
 pub struct Formatted(Vec<CharMap>);
 
+impl Formatted {
+	/// Returns a displayable version of the formatted text without colors,
+	/// even if the `colors` feature is enabled.
+	///
+	/// ```
+	/// # use source_span::{DEFAULT_METRICS, Span};
+	/// # use source_span::fmt::{Style, Formatter};
+	/// let text = "Hello World!";
+	/// let span = Span::of_string(text, &DEFAULT_METRICS);
+	///
+	/// let mut fmt = Formatter::new();
+	/// fmt.add(span, None, Style::Error);
+	///
+	/// let formatted = fmt.render(text.chars().map(Ok::<char, ()>), span, &DEFAULT_METRICS).unwrap();
+	/// println!("{}", formatted.plain());
+	/// ```
+	#[must_use]
+	pub const fn plain(&self) -> Plain<'_> { Plain(self) }
+
+	/// Returns the formatted text without colors.
+	///
+	/// Trailing whitespaces are removed from each line, which makes the result
+	/// suitable for comparison with a reference output (in snapshot tests for
+	/// instance).
+	#[must_use]
+	pub fn to_plain_string(&self) -> String {
+		let mut result = String::new();
+		for map in &self.0 {
+			for y in 0..map.height {
+				let line: String = (0..map.width).map(|x| map.get(x, y).unwrap()).collect();
+				result.push_str(line.trim_end());
+				result.push('\n');
+			}
+		}
+
+		result
+	}
+}
+
+/// Formatted text displayed without colors.
+///
+/// This is the result of the [`Formatted::plain`] function.
+pub struct Plain<'a>(&'a Formatted);
+
+impl<'a> fmt::Display for Plain<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for map in &(self.0).0 {
+			map.fmt_plain(f)?;
+		}
+
+		Ok(())
+	}
+}
+
 impl fmt::Display for Formatted {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		for map in &self.0 {
@@ -859,6 +1086,17 @@ impl fmt::Display for Formatted {
 	}
 }
 
+/// Number of decimal digits of the given number.
+fn digits(mut n: usize) -> usize {
+	let mut digits = 1;
+	while n >= 10 {
+		n /= 10;
+		digits += 1
+	}
+
+	digits
+}
+
 /// A set of important lines to render.
 pub enum ImportantLines {
 	All,
@@ -872,11 +1110,11 @@ impl ImportantLines {
 			ImportantLines::Lines(important_lines, viewbox) => important_lines
 				.binary_search_by(|candidate| {
 					use std::cmp::Ordering;
-					if line <= candidate + viewbox
-						&& line >= candidate - std::cmp::min(candidate, viewbox)
+					if line <= candidate.saturating_add(*viewbox)
+						&& line >= candidate.saturating_sub(*viewbox)
 					{
 						Ordering::Equal
-					} else if line <= candidate + viewbox {
+					} else if line <= candidate.saturating_add(*viewbox) {
 						Ordering::Greater
 					} else {
 						Ordering::Less
@@ -888,10 +1126,42 @@ impl ImportantLines {
 }
 
 impl Formatter {
-	fn important_lines(&self) -> ImportantLines {
+	/// Returns the highlights with their span clamped to the given rendered
+	/// span.
+	///
+	/// Highlights starting after the end of the rendered span are moved to
+	/// its end, and highlights ending after it are truncated.
+	fn clamped_highlights(&self, span: &Span) -> Cow<'_, [Highlight]> {
+		let clamp = |h: &Highlight| {
+			if h.span.start >= span.end {
+				span.end.into()
+			} else if h.span.last > span.last {
+				Span::try_new(h.span.start, span.last, span.end).unwrap_or_else(|_| span.end.into())
+			} else {
+				h.span
+			}
+		};
+
+		if self.highlights.iter().all(|h| clamp(h) == h.span) {
+			Cow::Borrowed(&self.highlights)
+		} else {
+			let mut highlights: Vec<_> = self
+				.highlights
+				.iter()
+				.map(|h| Highlight {
+					span: clamp(h),
+					..h.clone()
+				})
+				.collect();
+			highlights.sort_by(|a, b| a.span.containment_cmp(&b.span));
+			Cow::Owned(highlights)
+		}
+	}
+
+	fn important_lines(&self, highlights: &[Highlight]) -> ImportantLines {
 		if let Some(viewbox) = self.viewbox {
 			let mut important_lines = Vec::new();
-			for h in &self.highlights {
+			for h in highlights {
 				important_lines.push(h.span.start.line);
 				if h.span.start.line != h.span.last.line {
 					important_lines.push(h.span.last.line)
@@ -910,11 +1180,15 @@ impl Formatter {
 	/// If line numbers are disabled, this will return 0.
 	#[must_use]
 	pub fn margin_len(&self, span: &Span) -> usize {
+		self.highlights_margin_len(&self.clamped_highlights(span), span)
+	}
+
+	fn highlights_margin_len(&self, highlights: &[Highlight], span: &Span) -> usize {
 		if self.show_line_numbers {
 			let last_line = match self.viewbox {
 				Some(viewbox) => {
-					if let Some(last_highlight) = self.highlights.last() {
-						last_highlight.span.last().line + viewbox
+					if let Some(last_highlight) = highlights.last() {
+						last_highlight.span.last().line.saturating_add(viewbox)
 					} else {
 						return 0
 					}
@@ -922,7 +1196,7 @@ impl Formatter {
 				None => span.last().line
 			};
 
-			(((last_line + 1) as f32).log10() as usize) + 4
+			digits(last_line.saturating_add(1)) + 3
 		} else {
 			0
 		}
@@ -931,15 +1205,19 @@ impl Formatter {
 	/// Render the given input stream of character.
 	/// The result implements [`Display`](`fmt::Display`) and can then be printed.
 	///
+	/// This function never panics, whatever the input, span, highlights and
+	/// metrics.
+	/// Highlights outside of the rendered span are clamped to it, and
+	/// columns past the end of a line (such as `usize::MAX`) are drawn at the
+	/// end of the line.
+	///
 	/// ```
 	/// # use std::fs::File;
-	/// # use std::io::Read;
-	/// # use source_span::{DEFAULT_METRICS, SourceBuffer, Position};
+	/// # use source_span::{DEFAULT_METRICS, Encoding, SourceBuffer, Position};
 	/// # use source_span::fmt::{Color, Style, Formatter};
 	/// let file = File::open("examples/fib.txt").unwrap();
-	/// let chars = utf8_decode::UnsafeDecoder::new(file.bytes());
 	/// let metrics = DEFAULT_METRICS;
-	/// let buffer = SourceBuffer::new(chars, Position::default(), metrics);
+	/// let buffer = SourceBuffer::from_reader(file, Encoding::Utf8, Position::default(), metrics);
 	///
 	/// let mut fmt = Formatter::with_margin_color(Color::Blue);
 	/// fmt.add(buffer.span(), None, Style::Error);
@@ -953,9 +1231,201 @@ impl Formatter {
 		span: Span,
 		metrics: &M,
 	) -> Result<Formatted, E> {
-		let mut mapped_highlights = Vec::with_capacity(self.highlights.len());
+		let highlights = self.clamped_highlights(&span);
+		match self.mode {
+			Mode::Full => self.render_full(&highlights, input, span, metrics),
+			Mode::Short => Ok(Self::render_short(&highlights, self.source_name.as_deref())),
+			Mode::Compact => self.render_compact(&highlights, input, span, metrics),
+		}
+	}
+
+	fn render_short(highlights: &[Highlight], source_name: Option<&str>) -> Formatted {
+		let mut highlights: Vec<_> = highlights.iter().collect();
+		highlights.sort_by_key(|h| h.span.start);
+
+		let mut lines = Vec::with_capacity(highlights.len());
+		for h in highlights {
+			let mut line = CharMap::new();
+			let mut x = 0;
+			let mut text = |line: &mut CharMap, str: &str, color: Option<Color>| {
+				for c in str.chars() {
+					let c = match color {
+						Some(color) => Char::Label(c, color),
+						None => Char::Text(c),
+					};
+
+					line.set(x, 0, c);
+					x += 1
+				}
+			};
+
+			if let Some(name) = source_name {
+				text(&mut line, name, None);
+				text(&mut line, ":", None);
+			}
+
+			text(&mut line, &format!("{:?}: ", h.span.start), None);
+
+			if let Some(name) = h.style.name() {
+				text(&mut line, name, Some(h.style.color()));
+				text(&mut line, ": ", None);
+			}
+
+			if let Some(label) = &h.label {
+				for (i, label_line) in label.lines().enumerate() {
+					if i > 0 {
+						text(&mut line, " ", None);
+					}
+
+					text(&mut line, label_line, None);
+				}
+			}
+
+			lines.push(line)
+		}
+
+		Formatted(lines)
+	}
+
+	fn render_compact<E, I: Iterator<Item = Result<char, E>>, M: Metrics>(
+		&self,
+		highlights: &[Highlight],
+		input: I,
+		span: Span,
+		metrics: &M,
+	) -> Result<Formatted, E> {
+		let mut highlighted_lines = Vec::new();
+		for h in highlights {
+			highlighted_lines.push(h.span.start.line);
+			highlighted_lines.push(h.span.last.line);
+		}
+		highlighted_lines.sort_unstable();
+		highlighted_lines.dedup();
+
+		let margin_len = self.highlights_margin_len(highlights, &span);
+		let mut text_lines: Vec<CharMap> = highlighted_lines.iter().map(|_| CharMap::new()).collect();
+		let mut line_ends: Vec<usize> = highlighted_lines.iter().map(|_| 0).collect();
+
+		let mut pos = span.start();
+		let line_breaks = metrics.line_breaks();
+		let mut previous = None;
+		for c in input {
+			if pos > span.last() {
+				break;
+			}
+
+			let c = c?;
+			if previous.map(|p| line_breaks.continues(p, c)).unwrap_or(false) {
+				// the continuation of a line break sequence is part of the line break.
+				previous = Some(c);
+				continue;
+			}
+
+			match c {
+				c if line_breaks.is_line_break(c) => (),
+				'\t' => {
+					if let Ok(i) = highlighted_lines.binary_search(&pos.line) {
+						self.draw_tab(&mut text_lines[i], margin_len, pos, metrics)
+					}
+				}
+				_ => {
+					if let Ok(i) = highlighted_lines.binary_search(&pos.line) {
+						self.draw_char(&mut text_lines[i], margin_len, pos, c, metrics)
+					}
+				}
+			}
+
+			let next = pos.next(c, metrics);
+			if let Ok(i) = highlighted_lines.binary_search(&pos.line) {
+				line_ends[i] = if next.line == pos.line { next.column } else { pos.column }
+			}
+
+			pos = next;
+			previous = Some(c)
+		}
+
+		let mut lines = Vec::with_capacity(highlighted_lines.len());
+		let mut previous_line = None;
+		for ((line, mut charmap), line_end) in highlighted_lines.into_iter().zip(text_lines).zip(line_ends) {
+			if previous_line.map(|l: usize| l.saturating_add(1) < line).unwrap_or(false) && margin_len > 0 {
+				let mut viewbox_charmap = CharMap::new();
+				self.draw_line_number(None, &mut viewbox_charmap, margin_len);
+				lines.push(viewbox_charmap)
+			}
+
+			self.draw_line_number(Some(line), &mut charmap, margin_len);
+
+			// markers
+			let mut end = 0;
+			for h in highlights {
+				let (start, last) = if h.span.start.line == line && h.span.last.line == line {
+					(h.span.start.column, h.span.last.column)
+				} else if h.span.start.line == line {
+					(h.span.start.column, h.span.start.column)
+				} else if h.span.last.line == line {
+					(h.span.last.column, h.span.last.column)
+				} else {
+					continue
+				};
+				// columns past the end of the line are drawn at the end of the line.
+				let line_end = std::cmp::min(line_end, CHARMAP_MAX_SIZE);
+				let (start, last) = (std::cmp::min(start, line_end), std::cmp::min(last, line_end));
+
+				charmap.reserve(margin_len + last + 1, 2);
+				for x in start..=last {
+					if charmap.get(margin_len + x, 1).is_free() {
+						let c = if x == start || x == last {
+							Char::SpanMarker(h.style.marker(), h.style.color())
+						} else {
+							Char::SpanUnderline(h.style.line(), h.style.color())
+						};
+
+						charmap.set(margin_len + x, 1, c)
+					}
+				}
+
+				end = std::cmp::max(end, margin_len + last + 1);
+			}
+
+			// labels
+			let mut first_label = true;
+			for h in highlights {
+				if h.span.last.line == line {
+					if let Some(label) = &h.label {
+						if !first_label {
+							charmap.set(end, 1, Char::Text(','));
+							end += 1
+						}
+
+						end += 1;
+						for c in label.chars() {
+							let c = if c == '\n' { ' ' } else { c };
+							charmap.set(end, 1, Char::Label(c, h.style.color()));
+							end += 1
+						}
+
+						first_label = false
+					}
+				}
+			}
+
+			lines.push(charmap);
+			previous_line = Some(line)
+		}
+
+		Ok(Formatted(lines))
+	}
+
+	fn render_full<E, I: Iterator<Item = Result<char, E>>, M: Metrics>(
+		&self,
+		highlights: &[Highlight],
+		input: I,
+		span: Span,
+		metrics: &M,
+	) -> Result<Formatted, E> {
+		let mut mapped_highlights = Vec::with_capacity(highlights.len());
 		let mut nest_margin = 0;
-		for h in &self.highlights {
+		for h in highlights {
 			let margin_nest_level = h.margin_nest_level(&mapped_highlights);
 			// let start_nest_level = 0;
 			// let end_nest_level = h.end_nest_level(&mapped_highlights);
@@ -972,32 +1442,38 @@ impl Formatter {
 			});
 		}
 
-		let margin_len = self.margin_len(&span);
+		let margin_len = self.highlights_margin_len(highlights, &span);
 		let margin = margin_len + nest_margin;
 
 		let mut pos = span.start();
 		let mut lines = vec![CharMap::new()];
-		let important_lines = self.important_lines();
+		let important_lines = self.important_lines(highlights);
 		let mut is_important_line = important_lines.includes(pos.line);
 		if is_important_line {
 			lines.push(CharMap::new())
 		}
 		let mut first_non_whitespace = None;
+		let line_breaks = metrics.line_breaks();
+		let mut previous = None;
 		for c in input {
 			if pos > span.last() {
 				break;
 			}
 
 			let c = c?;
-			let x = margin + pos.column;
+			if previous.map(|p| line_breaks.continues(p, c)).unwrap_or(false) {
+				// the continuation of a line break sequence is part of the line break.
+				previous = Some(c);
+				continue;
+			}
 
 			match c {
-				'\n' => {
+				c if line_breaks.is_line_break(c) => {
 					if is_important_line {
 						let line_charmap = lines.last_mut().unwrap();
 						self.draw_line_number(Some(pos.line), line_charmap, margin_len);
 						self.draw_line_highlights(
-							pos.line,
+							pos,
 							line_charmap,
 							margin,
 							&mut mapped_highlights,
@@ -1006,12 +1482,12 @@ impl Formatter {
 						);
 					}
 					first_non_whitespace = None;
-					if important_lines.includes(pos.line + 1) {
+					if important_lines.includes(pos.line.saturating_add(1)) {
 						if !is_important_line && !lines.is_empty() {
 							let mut viewbox_charmap = CharMap::new();
 							self.draw_line_number(None, &mut viewbox_charmap, margin_len);
 							self.draw_line_highlights(
-								pos.line,
+								pos,
 								&mut viewbox_charmap,
 								margin,
 								&mut mapped_highlights,
@@ -1029,7 +1505,11 @@ impl Formatter {
 						lines.push(CharMap::new())
 					}
 				}
-				'\t' => (),
+				'\t' => {
+					if is_important_line {
+						self.draw_tab(lines.last_mut().unwrap(), margin, pos, metrics)
+					}
+				}
 				_ => {
 					if is_important_line {
 						if self.use_line_begining_shortcut
@@ -1039,19 +1519,20 @@ impl Formatter {
 							first_non_whitespace = Some(pos.column)
 						}
 
-						lines.last_mut().unwrap().set(x, 0, Char::Text(c))
+						self.draw_char(lines.last_mut().unwrap(), margin, pos, c, metrics)
 					}
 				}
 			}
 
-			pos.shift(c, metrics)
+			pos.shift(c, metrics);
+			previous = Some(c)
 		}
 
 		if is_important_line {
 			let line_charmap = lines.last_mut().unwrap();
 			self.draw_line_number(Some(pos.line), line_charmap, margin_len);
 			self.draw_line_highlights(
-				pos.line,
+				pos,
 				line_charmap,
 				margin,
 				&mut mapped_highlights,
@@ -1063,6 +1544,47 @@ impl Formatter {
 		Ok(Formatted(lines))
 	}
 
+	/// Draw the character located at the given position.
+	fn draw_char<M: Metrics>(
+		&self,
+		charmap: &mut CharMap,
+		margin: usize,
+		pos: Position,
+		c: char,
+		metrics: &M,
+	) {
+		match crate::visible_placeholder(c) {
+			Some(placeholder) if self.show_invisible_characters => {
+				let width = metrics.char_width(c);
+				let fits = placeholder.chars().count() <= width;
+				let mut chars = placeholder.chars();
+				for column in pos.column..pos.column.saturating_add(width) {
+					let c = if fits {
+						chars.next().unwrap_or(' ')
+					} else {
+						'\u{fffd}'
+					};
+
+					charmap.set(margin.saturating_add(column), 0, Char::Text(c))
+				}
+			}
+			_ => charmap.set(margin.saturating_add(pos.column), 0, Char::Text(c)),
+		}
+	}
+
+	/// Draw the tabulation located at the given position.
+	fn draw_tab<M: Metrics>(&self, charmap: &mut CharMap, margin: usize, pos: Position, metrics: &M) {
+		let end = pos.next('\t', metrics).column;
+		for column in pos.column..end {
+			let c = match self.tab_style {
+				TabStyle::Arrow if column == pos.column => '→',
+				_ => ' ',
+			};
+
+			charmap.set(margin.saturating_add(column), 0, Char::Text(c))
+		}
+	}
+
 	fn draw_line_number(
 		&self,
 		line: Option<usize>,
@@ -1071,16 +1593,16 @@ impl Formatter {
 	) {
 		if margin_len > 0 {
 			charmap.set(
-				margin_len - 2,
+				margin_len.saturating_sub(2),
 				0,
 				Char::Margin('|', self.margin_color),
 			);
 			match line {
 				Some(mut line) => {
-					let mut x = margin_len - 3;
-					line += 1;
+					let mut x = margin_len.saturating_sub(3);
+					line = line.saturating_add(1);
 
-					while line > 0 {
+					while line > 0 && x > 0 {
 						x -= 1;
 						let d = line % 10;
 
@@ -1097,7 +1619,7 @@ impl Formatter {
 					}
 				}
 				None => {
-					for x in 0..(margin_len - 3) {
+					for x in 0..margin_len.saturating_sub(3) {
 						charmap.set(x, 0, Char::Margin('.', self.margin_color))
 					}
 				}
@@ -1105,15 +1627,22 @@ impl Formatter {
 		}
 	}
 
+	/// Draw the highlights of the line ending at the given position.
 	fn draw_line_highlights<M: Metrics>(
 		&self,
-		line: usize,
+		line_end: Position,
 		charmap: &mut CharMap,
 		margin: usize,
 		highlights: &mut [MappedHighlight],
 		metrics: &M,
 		first_non_whitespace: Option<usize>,
 	) {
+		let line = line_end.line;
+
+		// columns past the end of the line (such as `usize::MAX`) are drawn at
+		// the end of the line.
+		let column_x = |column: usize| margin.saturating_add(std::cmp::min(column, line_end.column));
+
 		// span lines
 		for i in 0..highlights.len() {
 			let mut h = highlights[i];
@@ -1123,11 +1652,22 @@ impl Formatter {
 				h.update_start_nest_level(&highlights[0..i], first_non_whitespace);
 
 				if h.span().last.line == line {
+					// the lines of a span enclosing the label of another span
+					// would cross the label connector, so the span is only
+					// underlined.
+					let encloses_label = highlights.iter().enumerate().any(|(j, other)| {
+						j != i
+							&& other.label().is_some()
+							&& other.span().last.line == line
+							&& other.span().last.column > h.span().start.column
+							&& other.span().last.column < h.span().last.column
+					});
+
 					charmap.draw_closed_line(
 						h.style(),
-						h.start_nest_level,
-						margin + h.span().start.column,
-						margin + h.span().last.column,
+						if encloses_label { 1 } else { h.start_nest_level },
+						column_x(h.span().start.column),
+						column_x(h.span().last.column),
 					)
 				} else {
 					if first_non_whitespace.is_some()
@@ -1145,7 +1685,7 @@ impl Formatter {
 							h.style(),
 							h.start_nest_level,
 							margin - h.margin_nest_level + 1,
-							margin + h.span().start.column,
+							column_x(h.span().start.column),
 						)
 					}
 				}
@@ -1155,7 +1695,7 @@ impl Formatter {
 					h.style(),
 					h.end_nest_level,
 					margin - h.margin_nest_level + 1,
-					margin + h.span().last.column,
+					column_x(h.span().last.column),
 				);
 				// charmap.set(margin - h.margin_nest_level, h.end_nest_level,
 				// Char::SpanMargin(h.style().color()))
@@ -1168,11 +1708,11 @@ impl Formatter {
 					charmap.height() - 1
 				};
 
-				let x = margin - h.margin_nest_level;
+				let margin_x = margin - h.margin_nest_level;
 				let offset_y = if shortcut { 1 } else { 0 };
 
 				for y in offset_y..=end {
-					charmap.set(x, y, Char::SpanMargin(h.style().color()))
+					charmap.set(margin_x, y, Char::SpanMargin(h.style().color()))
 				}
 			}
 
@@ -1180,23 +1720,63 @@ impl Formatter {
 		}
 
 		// labels
-		for h in highlights.iter().rev() {
-			if h.span().last.line == line {
-				if let Some(label) = h.label() {
-					let label_charmap = CharMap::from_label(&label, h.style().color(), metrics);
-					let x = margin + h.span().last.column;
-					let mut y = 1;
-					if !charmap.draw_charmap_if_free(x + 2, y, &label_charmap) {
-						y += 2;
-						while !charmap.draw_charmap_if_free(x, y, &label_charmap) {
-							y += 1;
-						}
-					}
+		//
+		// Labels are placed from right to left (by end column), each one below
+		// the previously placed ones. Labels sharing an end column are stacked
+		// under a single connector, and a connector only goes through empty
+		// cells, so that it never crosses a label text or a span line.
+		let mut labeled: Vec<&MappedHighlight> = highlights
+			.iter()
+			.rev()
+			.filter(|h| h.span().last.line == line && h.label().is_some())
+			.collect();
+		labeled.sort_by_key(|h| std::cmp::Reverse(h.span().last.column));
+
+		let mut min_y = 3;
+		let mut start = 0;
+		while start < labeled.len() {
+			let column = labeled[start].span().last.column;
+			let len = labeled[start..].iter().take_while(|h| h.span().last.column == column).count();
+			let group = &labeled[start..(start + len)];
+			start += len;
+
+			let mut group_charmap = CharMap::from_label(group[0].label().unwrap(), group[0].style().color(), metrics);
+			for h in &group[1..] {
+				let label_charmap = CharMap::from_label(h.label().unwrap(), h.style().color(), metrics);
+				group_charmap.draw_charmap(0, group_charmap.height(), &label_charmap);
+			}
+
+			let h = group[0];
+			let x = column_x(h.span().last.column);
+			let y = if charmap.draw_charmap_if_free(x.saturating_add(2), 1, &group_charmap) {
+				1
+			} else {
+				// the connector cannot go past the first occupied cell under
+				// the marker.
+				let mut end_y = 2;
+				while end_y < charmap.height() && charmap.get(x, end_y).is_free_or_vertical() {
+					end_y += 1;
+				}
 
-					for vy in 2..y {
-						charmap.set(x, vy, Char::SpanVertical(h.style().color()));
+				let mut y = min_y;
+				while y <= end_y && !charmap.draw_charmap_if_free(x, y, &group_charmap) {
+					y += 1;
+				}
+
+				if y > end_y {
+					// no room before the obstacle, the connector goes through.
+					y = std::cmp::max(min_y, end_y + 1);
+					while !charmap.draw_charmap_if_free(x, y, &group_charmap) {
+						y += 1;
 					}
 				}
+
+				min_y = y + group_charmap.height();
+				y
+			};
+
+			for vy in 2..y {
+				charmap.set(x, vy, Char::SpanVertical(h.style().color()));
 			}
 		}
 	}
@@ -1206,13 +1786,78 @@ impl Default for Formatter {
 	fn default() -> Formatter {
 		Formatter {
 			highlights: Vec::new(),
-			#[cfg(not(feature = "colors"))]
-			margin_color: (),
-			#[cfg(feature = "colors")]
 			margin_color: Color::Blue,
 			viewbox: Some(2),
 			show_line_numbers: true,
 			use_line_begining_shortcut: true,
+			mode: Mode::Full,
+			source_name: None,
+			tab_style: TabStyle::Spaces,
+			show_invisible_characters: false,
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SOURCE: &str = "fn main() {\n    println!(\"Hello World!\")\n}\n";
+
+	fn render_with(fmt: &Formatter, source: &str) -> String {
+		let metrics = crate::DEFAULT_METRICS;
+		let span = Span::of_string(source, &metrics);
+		let formatted = fmt.render(source.chars().map(Ok::<char, ()>), span, &metrics).unwrap();
+		formatted.to_plain_string()
+	}
+
+	fn render(mode: Mode) -> String {
+		let mut fmt = Formatter::new();
+		fmt.set_mode(mode);
+		fmt.set_source_name(Some("main.rs".to_string()));
+		fmt.add(
+			Span::new(Position::new(0, 10), Position::new(2, 0), Position::new(2, 1)),
+			Some("a block".to_string()),
+			Style::Error,
+		);
+		fmt.add(
+			Span::new(Position::new(1, 13), Position::new(1, 26), Position::new(1, 27)),
+			Some("a string".to_string()),
+			Style::Note,
+		);
+
+		render_with(&fmt, SOURCE)
+	}
+
+	#[test]
+	fn render_short() {
+		assert_eq!(
+			render(Mode::Short),
+			"main.rs:1:11: error: a block\nmain.rs:2:14: note: a string\n"
+		)
+	}
+
+	#[test]
+	fn render_compact() {
+		assert_eq!(
+			render(Mode::Compact),
+			"1 | fn main() {\n  |           ^\n2 |     println!(\"Hello World!\")\n  |              -------------- a string\n3 | }\n  | ^ a block\n"
+		)
+	}
+
+	#[test]
+	fn render_plain() {
+		let metrics = crate::DEFAULT_METRICS;
+		let span = Span::of_string(SOURCE, &metrics);
+		let mut fmt = Formatter::new();
+		fmt.add(span, Some("everything".to_string()), Style::new('~', '^', Color::Red));
+
+		let formatted = fmt.render(SOURCE.chars().map(Ok::<char, ()>), span, &metrics).unwrap();
+		let plain = formatted.plain().to_string();
+		assert!(!plain.contains('\x1b'));
+		assert_eq!(
+			plain.lines().map(str::trim_end).collect::<Vec<_>>().join("\n") + "\n",
+			formatted.to_plain_string()
+		)
+	}
+}
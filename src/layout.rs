@@ -1,7 +1,12 @@
+use std::io::Read;
 use std::iter::Extend;
 use crate::{
+	Decoder,
+	DecodeError,
+	Encoding,
 	Position,
 	Span,
+	SpanError,
 	Metrics
 };
 
@@ -20,7 +25,10 @@ pub struct Layout<M: Metrics> {
 	metrics: M,
 
 	/// Byte length of the text.
-	len: usize
+	len: usize,
+
+	/// Last character of the text.
+	last_char: Option<char>
 }
 
 impl<M: Metrics> Layout<M> {
@@ -30,7 +38,8 @@ impl<M: Metrics> Layout<M> {
 			lines: vec![0],
 			span: Span::default(),
 			metrics,
-			len: 0
+			len: 0,
+			last_char: None
 		}
 	}
 
@@ -39,6 +48,11 @@ impl<M: Metrics> Layout<M> {
 		self.span
 	}
 
+	/// Get the metrics used by the layout.
+	pub fn metrics(&self) -> &M {
+		&self.metrics
+	}
+
 	/// Create a new layout from a `char` iterator.
 	pub fn from<Chars: Iterator<Item=char>>(chars: Chars, metrics: M) -> Layout<M> {
 		let mut layout = Layout::new(metrics);
@@ -57,13 +71,51 @@ impl<M: Metrics> Layout<M> {
 		Ok(layout)
 	}
 
+	/// Read and decode the given byte stream, and create the layout of the
+	/// decoded text.
+	///
+	/// Returns the decoded text with its layout.
+	/// Invalid byte sequences produce a [`DecodeError::InvalidSequence`] error
+	/// (see [`Decoder`] for a more configurable decoding).
+	///
+	/// ```
+	/// use source_span::{DEFAULT_METRICS, Encoding, Layout, Position};
+	///
+	/// let bytes: &[u8] = &[0xff, 0xfe, b'a', 0x00, b'\n', 0x00, 0x57, 0x5b];
+	/// let (text, layout) = Layout::from_reader(bytes, Encoding::Detect, DEFAULT_METRICS).unwrap();
+	///
+	/// assert_eq!(text, "a\n字");
+	/// assert_eq!(layout.byte_index(&text, Position::new(1, 0)), Some(2));
+	/// ```
+	pub fn from_reader<R: Read>(reader: R, encoding: Encoding, metrics: M) -> Result<(String, Layout<M>), DecodeError> where M: Clone {
+		let mut text = String::new();
+		let mut layout = Layout::new(metrics.clone());
+
+		for c in Decoder::new(reader, encoding, Position::default(), metrics) {
+			let c = c?;
+			text.push(c);
+			layout.push(c)
+		}
+
+		Ok((text, layout))
+	}
+
 	/// Extend the layout with a new character.
 	pub fn push(&mut self, c: char) {
-		self.span.push(c, &self.metrics);
+		let line_breaks = self.metrics.line_breaks();
 		self.len += c.len_utf8();
-		if c == '\n' {
-			self.lines.push(self.len)
+
+		if self.last_char.map(|p| line_breaks.continues(p, c)).unwrap_or(false) {
+			// the line starts after the whole line break sequence.
+			*self.lines.last_mut().unwrap() = self.len
+		} else {
+			self.span.push(c, &self.metrics);
+			if line_breaks.is_line_break(c) {
+				self.lines.push(self.len)
+			}
 		}
+
+		self.last_char = Some(c)
 	}
 
 	/// Get the byte index mapping to the given position in the input string slice.
@@ -73,27 +125,147 @@ impl<M: Metrics> Layout<M> {
 	/// the slice bounds.
 	pub fn byte_index(&self, str: &str, position: Position) -> Option<usize> {
 		if let Some(line_offset) = self.lines.get(position.line) {
-			let mut column = 0;
-			for (i, c) in str[*line_offset..].char_indices() {
-				if column == position.column {
+			let mut cursor = Position::new(position.line, 0);
+			for (i, c) in str.get(*line_offset..)?.char_indices() {
+				if cursor == position {
 					return Some(line_offset + i)
 				}
 
-				if c == '\n' {
+				if self.metrics.line_breaks().is_line_break(c) {
 					return None
 				}
 
-				column += self.metrics.char_width(c)
+				cursor.shift(c, &self.metrics)
 			}
 		}
 
 		None
 	}
 
+	/// Get the byte index mapping to the given position in the input string
+	/// slice, including the end position of the text.
+	///
+	/// If 0 columns wide characters are located at the end position, the index
+	/// of the first one is returned.
+	pub(crate) fn byte_index_or_end(&self, str: &str, position: Position) -> Option<usize> {
+		match self.byte_index(str, position) {
+			Some(index) => Some(index),
+			None if position == self.span.end() => Some(str.len()),
+			None => None
+		}
+	}
+
+	/// Get the position of the character at the given byte index in the input
+	/// string slice.
+	///
+	/// This is the inverse of [`byte_index`](Layout::byte_index).
+	/// The index of the end of the string slice is mapped to the end of the
+	/// layout's span.
+	/// Returns `None` if the index is out of bounds or not on a UTF8 character
+	/// boundary.
+	///
+	/// It is assumed that the input string slice matches the layout.
+	pub fn position(&self, str: &str, index: usize) -> Option<Position> {
+		if index > str.len() || !str.is_char_boundary(index) {
+			return None
+		}
+
+		let line = self.lines.partition_point(|offset| *offset <= index) - 1;
+		let line_offset = self.lines[line];
+		let mut cursor = Position::new(line, 0);
+		let mut previous = None;
+		for (i, c) in str[line_offset..].char_indices() {
+			if line_offset + i == index {
+				return Some(cursor)
+			}
+
+			cursor = cursor.next_after(previous, c, &self.metrics);
+			previous = Some(c)
+		}
+
+		Some(cursor)
+	}
+
+	/// Create the span from `start` to `end` (excluded) in the input string
+	/// slice, computing its last position from the text.
+	///
+	/// It is assumed that the input string slice matches the layout.
+	/// Panics if `end` is before `start` or if the positions are not the
+	/// positions of characters (or the end) of the text.
+	/// See [`try_span_between`](Layout::try_span_between) for a non-panicking
+	/// version.
+	///
+	/// ```
+	/// use source_span::{DEFAULT_METRICS, Layout, Position, Span};
+	///
+	/// let str = "let x =\t42;";
+	/// let layout = Layout::from(str.chars(), DEFAULT_METRICS);
+	///
+	/// let span = layout.span_between(str, Position::new(0, 4), Position::new(0, 8));
+	/// assert_eq!(span, Span::new(Position::new(0, 4), Position::new(0, 7), Position::new(0, 8)));
+	/// assert_eq!(layout.span_slice(str, span), "x =\t");
+	/// ```
+	pub fn span_between(&self, str: &str, start: Position, end: Position) -> Span {
+		match self.try_span_between(str, start, end) {
+			Ok(span) => span,
+			Err(e) => panic!("{}", e)
+		}
+	}
+
+	/// Create the span from `start` to `end` (excluded) in the input string
+	/// slice, computing its last position from the text.
+	///
+	/// It is assumed that the input string slice matches the layout.
+	/// Returns an error if `end` is before `start` or if the positions are not
+	/// the positions of characters (or the end) of the text.
+	pub fn try_span_between(&self, str: &str, start: Position, end: Position) -> Result<Span, SpanError> {
+		if end < start {
+			return Err(SpanError::Reversed(start, end))
+		}
+
+		let start_index = self.byte_index_or_end(str, start).ok_or(SpanError::InvalidPosition(start))?;
+		let end_index = self.byte_index_or_end(str, end).ok_or(SpanError::InvalidPosition(end))?;
+		self.span_of_range(str, start_index, end_index).ok_or(SpanError::InvalidPosition(start))
+	}
+
+	/// Get the span of the given range of byte indexes in the input string
+	/// slice.
+	///
+	/// Returns `None` if the range is out of bounds or not on UTF8 character
+	/// boundaries, or if it ends with a 0 columns wide character, in which case
+	/// its last position would not be before its end.
+	pub(crate) fn span_of_range(&self, str: &str, start: usize, end: usize) -> Option<Span> {
+		let start_pos = self.position(str, start)?;
+		let end_pos = self.position(str, end)?;
+
+		if start == end {
+			return Some(start_pos.into())
+		}
+
+		// find the last character, including multi-characters line breaks.
+		let mut chars = str.get(start..end)?.char_indices().rev();
+		let (mut last, c) = chars.next()?;
+		if let Some((i, previous)) = chars.next() {
+			if self.metrics.line_breaks().continues(previous, c) {
+				last = i
+			}
+		}
+
+		Span::try_new(start_pos, self.position(str, start + last)?, end_pos).ok()
+	}
+
 	/// Get the sub slice of the input string matching the given span.
+	///
+	/// Bounds of the span that are not positions of the text are clamped to
+	/// the text: the result is empty if the span starts after the end of the
+	/// text, and runs to the end of the text if the span ends after it.
+	/// This function never panics, even if the input string slice does not
+	/// match the layout (in which case the result is unspecified).
+	/// See [`try_span_slice`](Layout::try_span_slice) for a strict version.
 	pub fn span_slice<'a>(&self, str: &'a str, span: Span) -> &'a str {
 		let start = match self.byte_index(str, span.start) {
 			Some(index) => index,
+			None if span.start >= self.span.end() => str.len(),
 			None => 0
 		};
 
@@ -102,7 +274,31 @@ impl<M: Metrics> Layout<M> {
 			None => str.len()
 		};
 
-		&str[start..end]
+		str.get(start..end).unwrap_or("")
+	}
+
+	/// Get the sub slice of the input string matching the given span.
+	///
+	/// Returns an error if the bounds of the span are not positions of
+	/// characters (or the end) of the text, or if the input string slice does
+	/// not match the layout.
+	///
+	/// ```
+	/// use source_span::{DEFAULT_METRICS, Layout, Position, Span, SpanError};
+	///
+	/// let str = "let x = 42;";
+	/// let layout = Layout::from(str.chars(), DEFAULT_METRICS);
+	///
+	/// let span = Span::new(Position::new(0, 4), Position::new(0, 4), Position::new(0, 5));
+	/// assert_eq!(layout.try_span_slice(str, span), Ok("x"));
+	///
+	/// let span = Span::new(Position::new(0, 4), Position::new(1, 0), Position::new(1, 1));
+	/// assert_eq!(layout.try_span_slice(str, span), Err(SpanError::InvalidPosition(Position::new(1, 1))));
+	/// ```
+	pub fn try_span_slice<'a>(&self, str: &'a str, span: Span) -> Result<&'a str, SpanError> {
+		let start = self.byte_index_or_end(str, span.start).ok_or(SpanError::InvalidPosition(span.start))?;
+		let end = self.byte_index_or_end(str, span.end).ok_or(SpanError::InvalidPosition(span.end))?;
+		str.get(start..end).ok_or(SpanError::InvalidPosition(span.start))
 	}
 }
 
@@ -158,6 +354,83 @@ mod tests {
 		assert_eq!(layout.byte_index(str, Position::new(1, 3)), None);
 	}
 
+	#[test]
+	fn get_byte_index_unicode_line_breaks() {
+		let str = "Hel\r\nlo\rWor\u{2028}ld!";
+		let metrics = crate::DefaultMetrics::with_line_breaks(crate::LineBreaks::Unicode);
+		let layout = Layout::from(str.chars(), metrics);
+
+		assert_eq!(layout.span().end(), Position::new(3, 3));
+		assert_eq!(layout.byte_index(str, Position::new(1, 0)), Some(5));
+		assert_eq!(layout.byte_index(str, Position::new(2, 0)), Some(8));
+		assert_eq!(layout.byte_index(str, Position::new(3, 0)), Some(14));
+		assert_eq!(layout.byte_index(str, Position::new(0, 3)), Some(3));
+	}
+
+	#[test]
+	fn get_position() {
+		let str = "Hel\r\nl\to\rWor\u{2028}ld!";
+		let metrics = crate::DefaultMetrics::with_line_breaks(crate::LineBreaks::Unicode);
+		let layout = Layout::from(str.chars(), metrics);
+
+		for (i, _) in str.char_indices().chain(std::iter::once((str.len(), ' '))) {
+			let position = layout.position(str, i).unwrap();
+			if str[i..].starts_with('\n') {
+				assert_eq!(position, Position::new(1, 0))
+			} else {
+				assert_eq!(layout.byte_index(str, position).unwrap_or(str.len()), i)
+			}
+		}
+
+		assert_eq!(layout.position(str, str.len()), Some(layout.span().end()));
+		assert_eq!(layout.position(str, str.len() + 1), None);
+		assert_eq!(layout.position(str, 13), None);
+	}
+
+	#[test]
+	fn get_span_between_zero_width() {
+		// `\0` is 0 columns wide, at the end position.
+		let str = "a\0";
+		let layout = Layout::from(str.chars(), crate::DEFAULT_METRICS);
+		let end = layout.span().end();
+
+		assert_eq!(end, Position::new(0, 1));
+		assert_eq!(layout.try_span_between(str, Position::new(0, 0), end), Ok(Span::new(Position::new(0, 0), Position::new(0, 0), end)));
+		assert_eq!(layout.try_span_slice(str, layout.span()), Ok("a"));
+	}
+
+	#[test]
+	fn get_span_between() {
+		let str = "Hel\r\nl\to\r\n";
+		let metrics = crate::DefaultMetrics::with_line_breaks(crate::LineBreaks::Unicode);
+		let layout = Layout::from(str.chars(), metrics);
+
+		assert_eq!(
+			layout.try_span_between(str, Position::new(0, 1), Position::new(1, 0)),
+			Ok(Span::new(Position::new(0, 1), Position::new(0, 3), Position::new(1, 0)))
+		);
+		assert_eq!(
+			layout.try_span_between(str, Position::new(1, 0), Position::new(1, 8)),
+			Ok(Span::new(Position::new(1, 0), Position::new(1, 1), Position::new(1, 8)))
+		);
+		assert_eq!(
+			layout.try_span_between(str, Position::new(1, 0), layout.span().end()),
+			Ok(Span::new(Position::new(1, 0), Position::new(1, 9), Position::new(2, 0)))
+		);
+		assert_eq!(
+			layout.try_span_between(str, Position::new(1, 1), Position::new(1, 1)),
+			Ok(Position::new(1, 1).into())
+		);
+		assert_eq!(
+			layout.try_span_between(str, Position::new(1, 1), Position::new(0, 1)),
+			Err(SpanError::Reversed(Position::new(1, 1), Position::new(0, 1)))
+		);
+		assert_eq!(
+			layout.try_span_between(str, Position::new(1, 0), Position::new(1, 3)),
+			Err(SpanError::InvalidPosition(Position::new(1, 3)))
+		);
+	}
+
 	#[test]
 	fn get_span_slice1() {
 		let str = "Hello\nWorld!";
@@ -201,4 +474,12 @@ mod tests {
 		let span = Span::new(Position::new(0, 2), Position::new(2, 2), Position::new(2, 3));
 		assert_eq!(layout.span_slice(str, span), "l\nlo\nWor");
 	}
+
+	#[test]
+	fn get_span_slice_end() {
+		let str = "Hel\nlo\nWorld!";
+		let layout = Layout::from(str.chars(), crate::DEFAULT_METRICS);
+
+		assert_eq!(layout.span_slice(str, layout.span().end().into()), "");
+	}
 }
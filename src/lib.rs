@@ -55,38 +55,39 @@
 //! to index a character stream by character position.
 //!
 //! ```rust
-//! # use std::io::Read;
 //! use std::fs::File;
-//! use source_span::{DEFAULT_METRICS, Position, SourceBuffer};
+//! use source_span::{DEFAULT_METRICS, Encoding, Position, SourceBuffer};
 //!
 //! let file = File::open("examples/fib.txt").unwrap();
-//! let chars = utf8_decode::UnsafeDecoder::new(file.bytes());
 //! let metrics = DEFAULT_METRICS;
-//! let buffer = SourceBuffer::new(chars, Position::default(), metrics);
+//! let buffer = SourceBuffer::from_reader(file, Encoding::Utf8, Position::default(), metrics);
 //!
 //! buffer.at(Position::new(4, 2)); // get the character at line 4, column 2.
 //! ```
 //!
-//! The `SourceBuffer` type works as a wrapper around a character iterator.
+//! The `SourceBuffer` type works as a wrapper around a character iterator,
+//! here decoding the bytes of the file (UTF-8, UTF-16 and Latin-1 encodings
+//! are supported by the [`Decoder`]).
 //! It is lazy: new characters are pulled from the wrapped iterator and put in
 //! the buffer only when needed.
 //! It can be used to access characters at a specific cursor position (as seen
 //! above) or iterate a slice of the text using a `Span`:
 //!
 //! ```rust
-//! # use std::io::Read;
 //! # use std::fs::File;
-//! # use source_span::{DEFAULT_METRICS, Position, SourceBuffer};
+//! # use source_span::{DEFAULT_METRICS, Encoding, Position, SourceBuffer};
 //! # let file = File::open("examples/fib.txt").unwrap();
-//! # let chars = utf8_decode::UnsafeDecoder::new(file.bytes());
 //! # let metrics = DEFAULT_METRICS;
-//! # let buffer = SourceBuffer::new(chars, Position::default(), metrics);
+//! # let buffer = SourceBuffer::from_reader(file, Encoding::Utf8, Position::default(), metrics);
 //! # let span = buffer.span();
 //! for c in buffer.iter_span(span) {
 //!     // do something.
 //! }
 //! ```
 //!
+//! Lexers can be built on top of a `SourceBuffer` using a [`Scanner`], that
+//! keeps track of the span of the current token.
+//!
 //! ## Formatting
 //!
 //! This crate also provides a way to format decorated text, highlighting
@@ -106,20 +107,33 @@
 //! drawn with a specific style (defining what characters and color to use to
 //! draw the lines).
 #![warn(clippy::perf, clippy::must_use_candidate)]
+#![allow(clippy::tabs_in_doc_comments)]
 use std::cmp::{Ord, Ordering, PartialOrd};
 
 mod buffer;
+mod decode;
+mod display;
 pub mod fmt;
 mod loc;
+mod map;
 mod metrics;
 mod position;
 mod layout;
+mod parse;
+mod patch;
+mod source_map;
 
-pub use buffer::SourceBuffer;
+pub use buffer::{BufferError, Line, Scanner, ScannerMark, SourceBuffer, SourceError, SyncSourceBuffer};
+pub use decode::*;
+pub use display::*;
 pub use loc::Loc;
+pub use map::SpanMap;
 pub use metrics::*;
 pub use position::Position;
 pub use layout::*;
+pub use parse::*;
+pub use patch::*;
+pub use source_map::*;
 
 /// Span in a source file.
 ///
@@ -133,18 +147,29 @@ pub use layout::*;
 ///
 ///   * [`push`](Span::push) will extend the span to include the given character
 ///     located at the spans `end`.
+///   * [`push_after`](Span::push_after) does the same, counting line break
+///     sequences spanning multiple characters (such as `\r\n`) once.
 ///   * [`push_column`](Span::push_column) will extend the span to include the
-///     next column. Note that this does not
-/// necessarily correspond to the next character (if it is a NL, or a full-width
-/// character for instance).
+///     next column. Note that this does not necessarily correspond to the next
+///     character (if it is a NL, or a full-width character for instance).
 ///   * [`push_line`](Span::push_line) will extend the span to include the rest
-///     of the line. The end of the span will be
-/// placed at the begining of the next line.
+///     of the line. The end of the span will be placed at the begining of the
+///     next line.
 ///
 ///   * The [`next`](Span::next) method can finally be used to create the span
-///     to `[end, end]` (when a token has
-/// been read entirely for instance) and start building the next span. The
-/// [`clear`](Span::clear) method does the same but *in place*.
+///     to `[end, end]` (when a token has been read entirely for instance) and
+///     start building the next span. The [`clear`](Span::clear) method does
+///     the same but *in place*.
+///
+/// The [`Scanner`] type builds the spans of tokens read from a
+/// [`SourceBuffer`] this way.
+///
+/// ## Ordering
+///
+/// Spans are ordered by start position, and then by end position in reverse
+/// order (widest span first).
+/// The [`containment_cmp`](Span::containment_cmp) method compares spans by
+/// containment instead.
 ///
 /// ## Example
 ///
@@ -204,16 +229,13 @@ impl PartialOrd for Span {
 }
 
 impl Ord for Span {
+	/// Compares the start positions of the spans, and then their end
+	/// positions in reverse order, so that a span comes before the spans it
+	/// includes that start at the same position.
 	fn cmp(&self, other: &Self) -> Ordering {
-		if self == other {
-			Ordering::Equal
-		} else if self.includes(other) {
-			Ordering::Greater
-		} else if other.includes(self) {
-			Ordering::Less
-		} else {
-			self.start.cmp(&other.start)
-		}
+		self.start.cmp(&other.start)
+			.then_with(|| other.end.cmp(&self.end))
+			.then_with(|| other.last.cmp(&self.last))
 	}
 }
 
@@ -224,6 +246,9 @@ impl Span {
 	/// position then the returned span will be `[start, start]`.
 	/// If the `last` position is equal to `end` while the span is not empty, it
 	/// will panic.
+	/// See [`try_new`](Span::try_new) for a non-panicking version, or
+	/// [`Layout::span_between`] and [`SourceBuffer::span_between`] to
+	/// compute the `last` position from the source text.
 	#[must_use]
 	pub fn new(start: Position, mut last: Position, mut end: Position) -> Self {
 		if end < start || last < start {
@@ -238,12 +263,35 @@ impl Span {
 		Self { start, last, end }
 	}
 
+	/// Create a new span from three positions, checking that they are
+	/// consistent.
+	///
+	/// Returns an error if `end` is before `start`, or if `last` is not
+	/// between `start` (included) and `end` (excluded).
+	/// For an empty span (where `start` is equal to `end`), `last` must be
+	/// equal to `start`.
+	pub fn try_new(start: Position, last: Position, end: Position) -> Result<Self, SpanError> {
+		if end < start {
+			Err(SpanError::Reversed(start, end))
+		} else if (start == end && last != start) || (start != end && (last < start || last >= end)) {
+			Err(SpanError::InvalidLast(last))
+		} else {
+			Ok(Self { start, last, end })
+		}
+	}
+
 	pub fn of_string<M: Metrics>(str: &str, metrics: &M) -> Self {
 		let mut last = Position::new(0, 0);
 		let mut end = Position::new(0, 0);
+		let mut previous = None;
 		for c in str.chars() {
-			last = end;
-			end.shift(c, metrics)
+			// the continuation of a line break sequence is part of the line break.
+			if !previous.map(|p| metrics.line_breaks().continues(p, c)).unwrap_or(false) {
+				last = end;
+				end.shift(c, metrics)
+			}
+
+			previous = Some(c)
 		}
 
 		Self {
@@ -284,11 +332,28 @@ impl Span {
 		self.start <= other.start && self.last >= other.last
 	}
 
+	/// Compares two spans by containment, and then by start position.
+	///
+	/// A span is greater than the spans it [includes](Span::includes), so
+	/// that sorting spans with it puts inner spans before the spans including
+	/// them. Spans that do not include each other are ordered by start
+	/// position. Spans including each other (such as a single character span
+	/// and the empty span located at its start) are ordered by end position.
+	///
+	/// This is a total order, different from the [`Ord`] implementation.
+	#[must_use]
+	pub fn containment_cmp(&self, other: &Span) -> Ordering {
+		// spans that do not include each other are ordered the same way by
+		// their start and last positions.
+		(self.last, std::cmp::Reverse(self.start), self.end).cmp(&(other.last, std::cmp::Reverse(other.start), other.end))
+	}
+
 	/// The number of lines covered by the span.
 	///
-	/// It is at least one, even if the span is empty.
+	/// It is at least one, even if the span is empty, and saturates at
+	/// `usize::MAX`.
 	#[must_use]
-	pub const fn line_count(&self) -> usize { self.last.line - self.start.line + 1 }
+	pub const fn line_count(&self) -> usize { (self.last.line - self.start.line).saturating_add(1) }
 
 	/// Checks if the span includes the given line.
 	#[must_use]
@@ -316,29 +381,58 @@ impl Span {
 
 	/// Extend the span to include the given character located at the spans
 	/// `end` position.
+	///
+	/// Every character is pushed as a new position. To build a span character
+	/// by character with line break sequences spanning multiple characters
+	/// (such as `\r\n` with the [`LineBreaks::Unicode`] policy), use
+	/// [`push_after`](Span::push_after).
 	pub fn push<M: Metrics>(&mut self, c: char, metrics: &M) {
 		self.last = self.end;
 		self.end = self.end.next(c, metrics);
 	}
 
+	/// Extend the span to include the given character located at the spans
+	/// `end` position, directly following the `previous` character.
+	///
+	/// This is the same as [`push`](Span::push), except that the continuation
+	/// of a line break sequence is part of the line break: it does not move
+	/// the span, as in [`Position::next_after`].
+	///
+	/// ```
+	/// use source_span::{DefaultMetrics, LineBreaks, Position, Span};
+	///
+	/// let metrics = DefaultMetrics::with_line_breaks(LineBreaks::Unicode);
+	/// let mut span = Span::default();
+	/// let mut previous = None;
+	/// for c in "a\r\nb".chars() {
+	/// 	span.push_after(previous, c, &metrics);
+	/// 	previous = Some(c);
+	/// }
+	///
+	/// assert_eq!(span, Span::new(Position::new(0, 0), Position::new(1, 0), Position::new(1, 1)));
+	/// ```
+	pub fn push_after<M: Metrics>(&mut self, previous: Option<char>, c: char, metrics: &M) {
+		match previous {
+			Some(previous) if metrics.line_breaks().continues(previous, c) => (),
+			_ => self.push(c, metrics),
+		}
+	}
+
 	/// Compute the union of two spans.
 	///
 	/// If the two spans do not overlap, all positions in between will be
 	/// included in the resulting span.
+	/// If the span ending last is empty, the last position of the union is
+	/// approximated (see [`split_at`](Span::split_at)).
 	#[must_use]
 	pub fn union(&self, other: Self) -> Self {
-		if other.last > self.last && other.end > self.end {
-			Self {
-				start: std::cmp::min(self.start, other.start),
-				last: other.last,
-				end: other.end,
-			}
+		let start = std::cmp::min(self.start, other.start);
+		let last_span = if (other.end, other.last) > (self.end, self.last) { other } else { *self };
+
+		if last_span.is_empty() {
+			Self::until(start, last_span.end)
 		} else {
-			Self {
-				start: std::cmp::min(self.start, other.start),
-				last: self.last,
-				end: self.end,
-			}
+			Self::new(start, last_span.last, last_span.end)
 		}
 	}
 
@@ -347,10 +441,169 @@ impl Span {
 	/// If the two spans do not overlap, then the empty span located at the
 	/// start of the most advanced span (maximum of the start of the two
 	/// spans) is returned.
+	/// See [`intersection`](Span::intersection) to distinguish this case.
 	#[must_use]
 	pub fn inter(&self, other: Self) -> Self {
-		let start = std::cmp::max(self.start, other.start);
-		Self::new(start, other.last, other.end)
+		self.intersection(other)
+			.unwrap_or_else(|| std::cmp::max(self.start, other.start).into())
+	}
+
+	/// Computes the intersection of the two spans, if they
+	/// [overlap](Span::overlaps).
+	///
+	/// The intersection of an empty span with a span containing its position
+	/// is the empty span.
+	///
+	/// ```
+	/// use source_span::{Position, Span};
+	///
+	/// let a = Span::new(Position::new(0, 0), Position::new(0, 4), Position::new(0, 5));
+	/// let b = Span::new(Position::new(0, 2), Position::new(0, 9), Position::new(0, 10));
+	/// let c = Span::new(Position::new(0, 5), Position::new(0, 9), Position::new(0, 10));
+	///
+	/// assert_eq!(a.intersection(b), Some(Span::new(Position::new(0, 2), Position::new(0, 4), Position::new(0, 5))));
+	/// assert_eq!(a.intersection(c), None);
+	/// ```
+	#[must_use]
+	pub fn intersection(&self, other: Self) -> Option<Self> {
+		if self.overlaps(&other) {
+			let start = std::cmp::max(self.start, other.start);
+			let (end, last) = std::cmp::min((self.end, self.last), (other.end, other.last));
+
+			if start == end {
+				Some(start.into())
+			} else {
+				Some(Self::new(start, last, end))
+			}
+		} else {
+			None
+		}
+	}
+
+	/// Checks if the given position is in the span.
+	///
+	/// The [`end`](Span::end) of the span is not included, so an empty span
+	/// does not contain any position.
+	#[must_use]
+	pub fn contains(&self, pos: Position) -> bool {
+		self.start <= pos && pos < self.end
+	}
+
+	/// Checks if the two spans are adjacent, meaning that one ends exactly
+	/// where the other starts.
+	#[must_use]
+	pub fn adjacent(&self, other: &Span) -> bool {
+		self.end == other.start || other.end == self.start
+	}
+
+	/// Computes the parts of this span that are not in the `other` span.
+	///
+	/// Returns the part located before `other`, and the part located after
+	/// `other`, if they are not empty.
+	/// If the two spans do not overlap, `self` is returned as the part before
+	/// or after `other`.
+	///
+	/// Since characters metrics are unknown, the last position of the part
+	/// before `other` is approximated (see [`split_at`](Span::split_at)).
+	///
+	/// ```
+	/// use source_span::{Position, Span};
+	///
+	/// let a = Span::new(Position::new(0, 0), Position::new(0, 9), Position::new(0, 10));
+	/// let b = Span::new(Position::new(0, 2), Position::new(0, 4), Position::new(0, 5));
+	///
+	/// let (before, after) = a.difference(b);
+	/// assert_eq!(before, Some(Span::new(Position::new(0, 0), Position::new(0, 1), Position::new(0, 2))));
+	/// assert_eq!(after, Some(Span::new(Position::new(0, 5), Position::new(0, 9), Position::new(0, 10))));
+	/// ```
+	#[must_use]
+	pub fn difference(&self, other: Self) -> (Option<Self>, Option<Self>) {
+		if !self.overlaps(&other) {
+			if self.end <= other.start {
+				(Some(*self), None)
+			} else {
+				(None, Some(*self))
+			}
+		} else {
+			let before = if self.start < other.start {
+				Some(Self::until(self.start, other.start))
+			} else {
+				None
+			};
+
+			let after = if other.end < self.end {
+				Some(Self::new(other.end, self.last, self.end))
+			} else {
+				None
+			};
+
+			(before, after)
+		}
+	}
+
+	/// Splits the span at the given position.
+	///
+	/// Returns the part of the span before `pos`, and the part from `pos`, or
+	/// `None` if `pos` is not between the [`start`](Span::start) and
+	/// [`end`](Span::end) of the span.
+	///
+	/// Since characters metrics are unknown, the last position of the first
+	/// part is approximated as the column preceding `pos` or, if `pos` is at
+	/// the begining of a line, as the end of the previous line
+	/// (column `usize::MAX - 1`, as in [`aligned`](Span::aligned)).
+	///
+	/// ```
+	/// use source_span::{Position, Span};
+	///
+	/// let span = Span::new(Position::new(0, 0), Position::new(0, 9), Position::new(0, 10));
+	/// let (a, b) = span.split_at(Position::new(0, 4)).unwrap();
+	///
+	/// assert_eq!(a, Span::new(Position::new(0, 0), Position::new(0, 3), Position::new(0, 4)));
+	/// assert_eq!(b, Span::new(Position::new(0, 4), Position::new(0, 9), Position::new(0, 10)));
+	/// ```
+	#[must_use]
+	pub fn split_at(&self, pos: Position) -> Option<(Self, Self)> {
+		if pos < self.start || pos > self.end {
+			None
+		} else if pos == self.start {
+			Some((pos.into(), *self))
+		} else if pos == self.end {
+			Some((*self, pos.into()))
+		} else {
+			Some((Self::until(self.start, pos), Self::new(pos, self.last, self.end)))
+		}
+	}
+
+	/// Computes the span between two non-overlapping spans.
+	///
+	/// Returns `None` if the spans overlap or are adjacent.
+	/// As in [`split_at`](Span::split_at), the last position of the gap is
+	/// approximated.
+	#[must_use]
+	pub fn gap_between(&self, other: &Span) -> Option<Self> {
+		let (first, second) = if self.start <= other.start { (self, other) } else { (other, self) };
+		if first.end < second.start {
+			Some(Self::until(first.end, second.start))
+		} else {
+			None
+		}
+	}
+
+	/// Creates the span from `start` to `end`, approximating its last position
+	/// as the column preceding `end`, or the end of the previous line if `end`
+	/// is at the begining of a line.
+	pub(crate) fn until(start: Position, end: Position) -> Self {
+		let last = if end <= start {
+			start
+		} else if end.column == 0 {
+			std::cmp::max(start, Position::new(end.line - 1, usize::MAX - 1))
+		} else if end.column == usize::MAX {
+			std::cmp::max(start, Position::new(end.line, usize::MAX - 1))
+		} else {
+			std::cmp::max(start, Position::new(end.line, end.column - 1))
+		};
+
+		Self::new(start, last, end)
 	}
 
 	/// Extend the span to the end of the given span.
@@ -396,16 +649,57 @@ impl Span {
 			},
 			last: Position {
 				line: self.end.line,
-				column: usize::max_value() - 1,
+				column: usize::MAX - 1,
 			},
 			end: Position {
 				line: self.end.line,
-				column: usize::max_value(),
+				column: usize::MAX,
 			},
 		}
 	}
+
+	/// Returns a value displaying this span with the given format.
+	///
+	/// ```
+	/// use source_span::{Position, PositionFormat, PositionStyle, Span};
+	///
+	/// let span = Span::new(Position::new(0, 4), Position::new(0, 7), Position::new(0, 8));
+	/// let format = PositionFormat::new(1, PositionStyle::Gnu).with_file("main.rs");
+	/// assert_eq!(format!("{}: error", span.display_with(format)), "main.rs:1.5-8: error");
+	/// ```
+	#[must_use]
+	pub const fn display_with<'a>(self, format: PositionFormat<'a>) -> DisplaySpan<'a> {
+		DisplaySpan::new(self, format)
+	}
+}
+
+/// Span construction error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpanError {
+	/// The end of the span (second position) is before its start (first
+	/// position).
+	Reversed(Position, Position),
+
+	/// The last position of the span is not between its start and end.
+	InvalidLast(Position),
+
+	/// The position is not the position of a character, nor the end of the
+	/// text.
+	InvalidPosition(Position)
+}
+
+impl ::std::fmt::Display for SpanError {
+	fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+		match self {
+			SpanError::Reversed(start, end) => write!(f, "span ends ({:?}) before it starts ({:?})", end, start),
+			SpanError::InvalidLast(last) => write!(f, "invalid last position {:?}", last),
+			SpanError::InvalidPosition(pos) => write!(f, "no character at position {:?}", pos)
+		}
+	}
 }
 
+impl std::error::Error for SpanError {}
+
 impl From<Position> for Span {
 	fn from(pos: Position) -> Self {
 		Self {
@@ -429,6 +723,7 @@ impl ::std::fmt::Display for Span {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use proptest::prelude::*;
 
 	#[test]
 	fn test_display_span() {
@@ -442,4 +737,220 @@ mod tests {
 			"from 1:1 to 4:42".to_string()
 		);
 	}
+
+	fn valid(span: &Span) -> bool {
+		if span.is_empty() {
+			span.last == span.start
+		} else {
+			span.start <= span.last && span.last < span.end
+		}
+	}
+
+	// Spans are generated in a text of 4 lines of 6 one-column characters,
+	// each followed by a line break at column 6.
+	fn position() -> impl Strategy<Value = Position> {
+		(0..4usize, 0..7usize).prop_map(|(line, column)| Position::new(line, column))
+	}
+
+	prop_compose! {
+		fn span()(a in position(), b in position()) -> Span {
+			let (start, end) = if a <= b { (a, b) } else { (b, a) };
+			if start == end {
+				start.into()
+			} else if end.column == 0 {
+				Span::new(start, Position::new(end.line - 1, 6), end)
+			} else {
+				Span::new(start, Position::new(end.line, end.column - 1), end)
+			}
+		}
+	}
+
+	fn bounds(span: Option<Span>) -> Option<(Position, Position)> {
+		span.map(|s| (s.start, s.end))
+	}
+
+	proptest! {
+		#[test]
+		fn prop_intersection(a in span(), b in span(), p in position()) {
+			let inter = a.intersection(b);
+			prop_assert_eq!(inter.is_some(), a.overlaps(&b));
+			prop_assert_eq!(inter, b.intersection(a));
+			prop_assert_eq!(inter.map(|i| i.contains(p)).unwrap_or(false), a.contains(p) && b.contains(p));
+
+			if let Some(i) = inter {
+				prop_assert!(valid(&i));
+				prop_assert!(i.start >= a.start && i.start >= b.start);
+				prop_assert!(i.end <= a.end && i.end <= b.end);
+				prop_assert_eq!(a.inter(b), i);
+			} else {
+				prop_assert!(a.inter(b).is_empty());
+			}
+		}
+
+		#[test]
+		fn prop_difference(a in span(), b in span(), p in position()) {
+			let (before, after) = a.difference(b);
+			let contains = |s: Option<Span>| s.map(|s| s.contains(p)).unwrap_or(false);
+			prop_assert_eq!(contains(before) || contains(after), a.contains(p) && !b.contains(p));
+
+			for part in before.iter().chain(after.iter()) {
+				prop_assert!(valid(part));
+				prop_assert!(a.start <= part.start && part.end <= a.end);
+				prop_assert!(!part.overlaps(&b) || b.is_empty());
+			}
+
+			if let Some(before) = before {
+				prop_assert!(before.end <= b.start || !a.overlaps(&b));
+			}
+
+			if let Some(after) = after {
+				prop_assert!(after.start >= b.end || !a.overlaps(&b));
+			}
+		}
+
+		#[test]
+		fn prop_split_at(a in span(), pos in position(), p in position()) {
+			match a.split_at(pos) {
+				Some((left, right)) => {
+					prop_assert!(valid(&left) && valid(&right));
+					prop_assert_eq!((left.start, left.end), (a.start, pos));
+					prop_assert_eq!((right.start, right.end), (pos, a.end));
+					prop_assert!(left.adjacent(&right));
+					prop_assert_eq!(left.contains(p) || right.contains(p), a.contains(p));
+					prop_assert_eq!(bounds(Some(left.union(right))), bounds(Some(a)));
+				},
+				None => prop_assert!(pos < a.start || pos > a.end)
+			}
+		}
+
+		#[test]
+		fn prop_gap_between(a in span(), b in span(), p in position()) {
+			let gap = a.gap_between(&b);
+			prop_assert_eq!(bounds(gap), bounds(b.gap_between(&a)));
+
+			match gap {
+				Some(gap) => {
+					prop_assert!(valid(&gap));
+					prop_assert!(!gap.is_empty());
+					prop_assert!(a.is_empty() || !gap.overlaps(&a));
+					prop_assert!(b.is_empty() || !gap.overlaps(&b));
+					prop_assert!(gap.adjacent(&a) && gap.adjacent(&b));
+					prop_assert!(!(gap.contains(p) && (a.contains(p) || b.contains(p))));
+				},
+				None => prop_assert!(a.overlaps(&b) || a.adjacent(&b) || a.end >= b.start && b.end >= a.start)
+			}
+		}
+
+		#[test]
+		fn prop_union(a in span(), b in span()) {
+			let union = a.union(b);
+			prop_assert!(valid(&union));
+			prop_assert_eq!(bounds(Some(union)), bounds(Some(b.union(a))));
+			prop_assert_eq!(union.start, std::cmp::min(a.start, b.start));
+			prop_assert_eq!(union.end, std::cmp::max(a.end, b.end));
+		}
+
+		#[test]
+		fn prop_contains(a in span(), p in position()) {
+			prop_assert_eq!(a.contains(p), a.start <= p && p < a.end);
+			prop_assert_eq!(a.adjacent(&a.next()), true);
+		}
+	}
+
+	#[test]
+	fn test_ord_span() {
+		let span = |a, b| Span::new(Position::new(0, a), Position::new(0, b - 1), Position::new(0, b));
+
+		// a single character span and the empty span at its start include
+		// each other.
+		let empty: Span = Position::new(0, 0).into();
+		assert_eq!(span(0, 1).containment_cmp(&empty), Ordering::Greater);
+		assert_eq!(empty.containment_cmp(&span(0, 1)), Ordering::Less);
+		assert!(empty > span(0, 1));
+
+		let a = span(0, 4);
+		let b = span(2, 6);
+		let c = span(3, 4);
+		assert_eq!(a.containment_cmp(&c), Ordering::Greater);
+		assert!(a < b && b < c && a < c);
+		assert!(span(0, 4) < span(0, 2));
+
+		let mut spans = vec![c, b, a, span(0, 2)];
+		spans.sort();
+		assert_eq!(spans, vec![a, span(0, 2), b, c]);
+	}
+
+	proptest! {
+		#[test]
+		fn prop_ord(a in span(), b in span(), c in span()) {
+			// total and antisymmetric, consistent with `Eq`.
+			prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+			prop_assert_eq!(a.cmp(&b) == Ordering::Equal, a == b);
+
+			// transitive.
+			if a <= b && b <= c {
+				prop_assert!(a <= c);
+			}
+
+			// lexicographic on start, then reversed end.
+			if a.start != b.start || a.end != b.end {
+				prop_assert_eq!(a.cmp(&b), (a.start, std::cmp::Reverse(a.end)).cmp(&(b.start, std::cmp::Reverse(b.end))));
+			}
+		}
+
+		#[test]
+		fn prop_containment_cmp(a in span(), b in span(), c in span()) {
+			// total and antisymmetric, consistent with `Eq`.
+			prop_assert_eq!(a.containment_cmp(&b), b.containment_cmp(&a).reverse());
+			prop_assert_eq!(a.containment_cmp(&b) == Ordering::Equal, a == b);
+
+			// transitive.
+			if a.containment_cmp(&b) != Ordering::Greater && b.containment_cmp(&c) != Ordering::Greater {
+				prop_assert!(a.containment_cmp(&c) != Ordering::Greater);
+			}
+
+			// a span is greater than the spans it includes, the other spans
+			// are ordered by start position.
+			if a.includes(&b) && !b.includes(&a) {
+				prop_assert_eq!(a.containment_cmp(&b), Ordering::Greater);
+			} else if !a.includes(&b) && !b.includes(&a) {
+				prop_assert_eq!(a.containment_cmp(&b), a.start.cmp(&b.start));
+			}
+		}
+	}
+
+	#[test]
+	fn test_try_new() {
+		let p = |column| Position::new(0, column);
+		assert_eq!(Span::try_new(p(1), p(2), p(3)), Ok(Span::new(p(1), p(2), p(3))));
+		assert_eq!(Span::try_new(p(1), p(1), p(1)), Ok(p(1).into()));
+		assert_eq!(Span::try_new(p(3), p(2), p(1)), Err(SpanError::Reversed(p(3), p(1))));
+		assert_eq!(Span::try_new(p(1), p(3), p(3)), Err(SpanError::InvalidLast(p(3))));
+		assert_eq!(Span::try_new(p(1), p(0), p(3)), Err(SpanError::InvalidLast(p(0))));
+		assert_eq!(Span::try_new(p(1), p(2), p(1)), Err(SpanError::InvalidLast(p(2))));
+	}
+
+	#[test]
+	fn test_push_crlf() {
+		let metrics = DefaultMetrics::with_line_breaks(LineBreaks::Unicode);
+		let text = "a\r\nb\r\n";
+
+		let mut span = Span::default();
+		let mut previous = None;
+		for c in text.chars() {
+			span.push_after(previous, c, &metrics);
+			previous = Some(c)
+		}
+
+		assert_eq!(span, Span::of_string(text, &metrics));
+		assert_eq!(span, Span::new(Position::new(0, 0), Position::new(1, 1), Position::new(2, 0)));
+		assert_eq!(span, Layout::from(text.chars(), metrics).span());
+
+		// `push` counts every character.
+		let mut span = Span::default();
+		for c in text.chars() {
+			span.push(c, &metrics)
+		}
+		assert_eq!(span.end(), Position::new(4, 0));
+	}
 }
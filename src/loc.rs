@@ -34,7 +34,7 @@ impl<T: ?Sized> Loc<T> {
 	/// Associate a span location to some data by wrapping it under `Loc`.
 	pub fn new(t: T, span: Span) -> Loc<T> where T: Sized {
 		Self {
-			span: span,
+			span,
 			value: t
 		}
 	}
@@ -0,0 +1,302 @@
+use std::iter::FromIterator;
+use crate::{
+	Loc,
+	Position,
+	Span
+};
+
+/// Interval index of located values.
+///
+/// Stores [`Loc`] values ordered by span (see the [`Ord`] implementation of
+/// [`Span`]) and answers the following queries
+/// in `O((k + 1) log n)` time, where `k` is the number of candidate values
+/// (the returned values, plus the values whose span only touches the query
+/// bounds or is empty, that are filtered out of the results):
+///
+///  * [`at`](SpanMap::at) returns the values whose span contains a position,
+///  * [`overlapping`](SpanMap::overlapping) returns the values whose span
+///    overlaps a given span,
+///  * [`enclosing`](SpanMap::enclosing) returns the values whose span encloses a
+///    given span.
+///
+/// Results are always returned in position order.
+///
+/// ```
+/// use source_span::{Loc, Position, Span, SpanMap};
+///
+/// let span = |a, b| Span::new(Position::new(0, a), Position::new(0, b - 1), Position::new(0, b));
+///
+/// let map: SpanMap<&str> = vec![
+/// 	Loc::new("call", span(0, 10)),
+/// 	Loc::new("arg1", span(2, 4)),
+/// 	Loc::new("arg2", span(6, 9))
+/// ].into_iter().collect();
+///
+/// let nodes: Vec<_> = map.at(Position::new(0, 7)).map(|loc| *loc.as_ref()).collect();
+/// assert_eq!(nodes, ["call", "arg2"]);
+/// assert_eq!(map.innermost(Position::new(0, 3)).map(|loc| *loc.as_ref()), Some("arg1"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SpanMap<T> {
+	/// Values sorted by position.
+	items: Vec<Loc<T>>,
+
+	/// Maximum end position of each sub-tree of the implicit binary search
+	/// tree over `items`.
+	///
+	/// The sub-tree rooted at index `mid` covers the range `lo..hi` of items
+	/// such that `mid = (lo + hi) / 2`.
+	max_ends: Vec<Position>
+}
+
+impl<T> SpanMap<T> {
+	/// Create a new empty map.
+	#[must_use]
+	pub fn new() -> Self {
+		Self {
+			items: Vec::new(),
+			max_ends: Vec::new()
+		}
+	}
+
+	/// Returns the number of values in the map.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.items.len()
+	}
+
+	/// Checks if the map is empty.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.items.is_empty()
+	}
+
+	/// Insert a value located at the given span.
+	///
+	/// This takes `O(n)` time. Use the [`FromIterator`] or [`Extend`]
+	/// implementations to insert many values at once.
+	pub fn insert(&mut self, span: Span, value: T) {
+		let i = self.items.partition_point(|item| item.span() <= span);
+		self.items.insert(i, Loc::new(value, span));
+		self.index()
+	}
+
+	/// Returns an iterator over the values of the map, in position order.
+	pub fn iter(&self) -> std::slice::Iter<'_, Loc<T>> {
+		self.items.iter()
+	}
+
+	/// Returns the values whose span contains the given position, in position
+	/// order.
+	///
+	/// Values with an empty span do not contain any position.
+	pub fn at(&self, pos: Position) -> impl '_ + Iterator<Item = &Loc<T>> {
+		self.search(pos, pos, move |span| span.contains(pos))
+	}
+
+	/// Returns the value with the narrowest span containing the given position.
+	///
+	/// This is the span ending first and, among them, the span starting last.
+	/// If the spans containing the position are nested (like the nodes of an
+	/// abstract syntax tree), this is the innermost one.
+	#[must_use]
+	pub fn innermost(&self, pos: Position) -> Option<&Loc<T>> {
+		self.at(pos).min_by_key(|item| (item.span().end(), std::cmp::Reverse(item.span().start())))
+	}
+
+	/// Returns the values whose span [overlaps](Span::overlaps) the given
+	/// span, in position order.
+	pub fn overlapping(&self, span: Span) -> impl '_ + Iterator<Item = &Loc<T>> {
+		self.search(span.start(), span.end(), move |other| other.overlaps(&span))
+	}
+
+	/// Returns the values whose span encloses the given span, meaning that it
+	/// starts before (or with) and ends after (or with) the given span, in
+	/// position order.
+	pub fn enclosing(&self, span: Span) -> impl '_ + Iterator<Item = &Loc<T>> {
+		self.search(span.end(), span.start(), move |other| other.start() <= span.start() && span.end() <= other.end())
+	}
+
+	/// Returns the values with an end position greater or equal to `min_end`,
+	/// a start position lower or equal to `max_start` and a span matching the
+	/// given predicate.
+	fn search<F: 'static + Fn(&Span) -> bool>(&self, min_end: Position, max_start: Position, f: F) -> impl '_ + Iterator<Item = &Loc<T>> {
+		let mut indexes = Vec::new();
+		self.search_in(0, self.items.len(), min_end, max_start, &mut indexes);
+		indexes.into_iter()
+			.map(move |i| &self.items[i])
+			.filter(move |item| f(&item.span()))
+	}
+
+	fn search_in(&self, lo: usize, hi: usize, min_end: Position, max_start: Position, indexes: &mut Vec<usize>) {
+		if lo < hi {
+			let mid = (lo + hi) / 2;
+			if self.max_ends[mid] >= min_end {
+				self.search_in(lo, mid, min_end, max_start, indexes);
+
+				// items are sorted by start position.
+				if self.items[mid].span().start() <= max_start {
+					if self.items[mid].span().end() >= min_end {
+						indexes.push(mid)
+					}
+
+					self.search_in(mid + 1, hi, min_end, max_start, indexes)
+				}
+			}
+		}
+	}
+
+	/// Compute the maximum end of each sub-tree.
+	fn index(&mut self) {
+		self.max_ends = vec![Position::default(); self.items.len()];
+		self.index_in(0, self.items.len());
+	}
+
+	fn index_in(&mut self, lo: usize, hi: usize) -> Option<Position> {
+		if lo < hi {
+			let mid = (lo + hi) / 2;
+			let mut max_end = self.items[mid].span().end();
+
+			if let Some(end) = self.index_in(lo, mid) {
+				max_end = std::cmp::max(max_end, end)
+			}
+
+			if let Some(end) = self.index_in(mid + 1, hi) {
+				max_end = std::cmp::max(max_end, end)
+			}
+
+			self.max_ends[mid] = max_end;
+			Some(max_end)
+		} else {
+			None
+		}
+	}
+}
+
+impl<T> Default for SpanMap<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> FromIterator<Loc<T>> for SpanMap<T> {
+	fn from_iter<I: IntoIterator<Item = Loc<T>>>(iter: I) -> Self {
+		let mut map = Self::new();
+		map.extend(iter);
+		map
+	}
+}
+
+impl<T> Extend<Loc<T>> for SpanMap<T> {
+	fn extend<I: IntoIterator<Item = Loc<T>>>(&mut self, iter: I) {
+		self.items.extend(iter);
+		self.items.sort_by_key(Loc::span);
+		self.index()
+	}
+}
+
+impl<'a, T> IntoIterator for &'a SpanMap<T> {
+	type Item = &'a Loc<T>;
+	type IntoIter = std::slice::Iter<'a, Loc<T>>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.items.iter()
+	}
+}
+
+impl<T> IntoIterator for SpanMap<T> {
+	type Item = Loc<T>;
+	type IntoIter = std::vec::IntoIter<Loc<T>>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.items.into_iter()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use proptest::prelude::*;
+
+	fn position() -> impl Strategy<Value = Position> {
+		(0..4usize, 0..7usize).prop_map(|(line, column)| Position::new(line, column))
+	}
+
+	prop_compose! {
+		fn span()(a in position(), b in position()) -> Span {
+			let (start, end) = if a <= b { (a, b) } else { (b, a) };
+			if start == end {
+				start.into()
+			} else {
+				Span::new(start, start, end)
+			}
+		}
+	}
+
+	fn values<'a>(items: impl Iterator<Item = &'a Loc<usize>>) -> Vec<usize> {
+		items.map(|item| *item.as_ref()).collect()
+	}
+
+	proptest! {
+		#[test]
+		fn prop_queries(spans in prop::collection::vec(span(), 0..40), query in span(), pos in position()) {
+			let map: SpanMap<usize> = spans.iter().enumerate().map(|(i, span)| Loc::new(i, *span)).collect();
+			prop_assert_eq!(map.len(), spans.len());
+
+			let sorted = values(map.iter());
+			prop_assert!(sorted.windows(2).all(|w| spans[w[0]] <= spans[w[1]]));
+
+			let expected = |f: &dyn Fn(&Span) -> bool| -> Vec<usize> {
+				sorted.iter().copied().filter(|i| f(&spans[*i])).collect()
+			};
+
+			prop_assert_eq!(values(map.at(pos)), expected(&|s| s.contains(pos)));
+			prop_assert_eq!(values(map.overlapping(query)), expected(&|s| s.overlaps(&query)));
+			prop_assert_eq!(values(map.enclosing(query)), expected(&|s| s.start() <= query.start() && query.end() <= s.end()));
+
+			// no span containing the position ends before the innermost one.
+			if let Some(innermost) = map.innermost(pos) {
+				for item in map.at(pos) {
+					prop_assert!(innermost.span().end() <= item.span().end());
+				}
+			}
+		}
+
+		#[test]
+		fn prop_insert(spans in prop::collection::vec(span(), 0..20), pos in position()) {
+			let mut map = SpanMap::new();
+			for (i, span) in spans.iter().enumerate() {
+				map.insert(*span, i)
+			}
+
+			let collected: SpanMap<usize> = spans.iter().enumerate().map(|(i, span)| Loc::new(i, *span)).collect();
+			prop_assert_eq!(values(map.iter()), values(collected.iter()));
+			prop_assert_eq!(values(map.at(pos)), values(collected.at(pos)));
+		}
+	}
+
+	#[test]
+	fn innermost() {
+		let span = |a, b| Span::new(Position::new(0, a), Position::new(0, b - 1), Position::new(0, b));
+		let map: SpanMap<usize> = vec![
+			Loc::new(0, span(0, 20)),
+			Loc::new(1, span(2, 10)),
+			Loc::new(2, span(4, 6)),
+			Loc::new(3, span(12, 18))
+		].into_iter().collect();
+
+		assert_eq!(map.innermost(Position::new(0, 5)).map(|l| *l.as_ref()), Some(2));
+		assert_eq!(map.innermost(Position::new(0, 8)).map(|l| *l.as_ref()), Some(1));
+		assert_eq!(map.innermost(Position::new(0, 19)).map(|l| *l.as_ref()), Some(0));
+		assert_eq!(map.innermost(Position::new(0, 20)).map(|l| *l.as_ref()), None);
+
+		// overlapping spans that are not nested.
+		let map: SpanMap<usize> = vec![
+			Loc::new(0, span(0, 3)),
+			Loc::new(1, span(1, 10))
+		].into_iter().collect();
+
+		assert_eq!(map.innermost(Position::new(0, 2)).map(|l| *l.as_ref()), Some(0));
+		assert_eq!(map.innermost(Position::new(0, 3)).map(|l| *l.as_ref()), Some(1));
+	}
+}
@@ -1,24 +1,93 @@
 /// Default character metrics.
 pub static DEFAULT_METRICS: DefaultMetrics = DefaultMetrics::new();
 
-/// Gives the size of each character and tab stop length.
+/// Gives the size of each character, tab stop length and line breaks.
 ///
 /// This is used to correctly compute spans in the source text,
 /// and render the text using a [`Formatter`](`crate::fmt::Formatter`).
 pub trait Metrics {
 	/// Get the size (width in columns) of a character.
+	///
+	/// This is also used for control characters (other than tabulations and
+	/// line breaks), that [`DefaultMetrics`] gives a width of 0.
 	fn char_width(&self, c: char) -> usize;
 
 	/// Get the tab stop length.
+	///
+	/// A tab stop length of 0 is treated as 1.
 	fn tab_stop(&self) -> usize;
+
+	/// Get the line break policy.
+	///
+	/// By default, only `\n` is a line break ([`LineBreaks::Unix`]).
+	fn line_breaks(&self) -> LineBreaks { LineBreaks::Unix }
+}
+
+/// Line break policy.
+///
+/// Defines which characters, or sequence of characters, end a line.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum LineBreaks {
+	/// Only the `\n` (LF) character is a line break (Unix semantics).
+	///
+	/// Note that the `\r` (CR) character is a 0-width control character with
+	/// the [`DefaultMetrics`], so `\r\n` (CRLF) line breaks are also supported.
+	#[default]
+	Unix,
+
+	/// Any Unicode line break: `\n` (LF), `\r` (CR), the `\r\n` (CRLF)
+	/// sequence, vertical tab, form feed, `U+0085` (NEL), `U+2028` (LS) and
+	/// `U+2029` (PS).
+	///
+	/// This is the definition used by ECMAScript and XML (among others).
+	Unicode,
+}
+
+impl LineBreaks {
+	/// Checks if the given character is a line break.
+	#[must_use]
+	pub const fn is_line_break(&self, c: char) -> bool {
+		match self {
+			Self::Unix => c == '\n',
+			Self::Unicode => matches!(
+				c,
+				'\n' | '\r' | '\u{b}' | '\u{c}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+			),
+		}
+	}
+
+	/// Checks if the character `c` directly following `previous` is the
+	/// continuation of a line break sequence started by `previous`.
+	///
+	/// This is only the case of the `\n` character in the `\r\n` sequence with
+	/// the [`Unicode`](LineBreaks::Unicode) policy, where both characters form
+	/// a single line break.
+	#[must_use]
+	pub const fn continues(&self, previous: char, c: char) -> bool {
+		self.may_continue(previous) && c == '\n'
+	}
+
+	/// Checks if a line break sequence may continue after the given character.
+	///
+	/// When this is `false`, no character following `c` is a
+	/// [continuation](LineBreaks::continues) of `c`, so there is no need to
+	/// look at the next character.
+	#[must_use]
+	pub const fn may_continue(&self, c: char) -> bool {
+		matches!(self, Self::Unicode) && c == '\r'
+	}
 }
 
 /// Default metrics infos.
 ///
 /// By default, tab stop length is 8, but it can be set using [`DefaultMetrics::with_tab_stop`].
+/// Control characters are 0-width, any other character is 1 column wide.
+/// Line breaks follow the [`LineBreaks::Unix`] policy by default, but it can be
+/// set using [`DefaultMetrics::with_line_breaks`].
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct DefaultMetrics {
 	tab_stop: usize,
+	line_breaks: LineBreaks,
 }
 
 impl DefaultMetrics {
@@ -30,16 +99,113 @@ impl DefaultMetrics {
 
 	/// Create a new default metrics with a custom tab stop length.
 	#[must_use]
-	pub const fn with_tab_stop(tab_stop: usize) -> DefaultMetrics { DefaultMetrics { tab_stop } }
+	pub const fn with_tab_stop(tab_stop: usize) -> DefaultMetrics {
+		DefaultMetrics {
+			tab_stop,
+			line_breaks: LineBreaks::Unix,
+		}
+	}
+
+	/// Create a new default metrics with a custom line break policy.
+	///
+	/// Tab stop length will be 8.
+	#[must_use]
+	pub const fn with_line_breaks(line_breaks: LineBreaks) -> DefaultMetrics {
+		DefaultMetrics {
+			tab_stop: 8,
+			line_breaks,
+		}
+	}
+
+	/// Set the tab stop length.
+	pub fn set_tab_stop(&mut self, tab_stop: usize) { self.tab_stop = tab_stop }
+
+	/// Set the line break policy.
+	pub fn set_line_breaks(&mut self, line_breaks: LineBreaks) { self.line_breaks = line_breaks }
+}
+
+impl Default for DefaultMetrics {
+	fn default() -> Self { Self::new() }
 }
 
 impl Metrics for DefaultMetrics {
 	fn char_width(&self, c: char) -> usize {
-		match c {
-			'\r' | '\n' => 0,
-			_ => 1,
+		if c.is_control() {
+			0
+		} else {
+			1
 		}
 	}
 
 	fn tab_stop(&self) -> usize { self.tab_stop }
+
+	fn line_breaks(&self) -> LineBreaks { self.line_breaks }
+}
+
+/// Metrics making invisible and control characters visible.
+///
+/// Invisible and control characters (except new lines and tabulations) are
+/// given the width of their [`visible_placeholder`]. Any other character is
+/// measured using the wrapped metrics.
+///
+/// Spans computed with these metrics can be rendered with a
+/// [`Formatter`](`crate::fmt::Formatter`) showing invisible characters (see
+/// [`Formatter::set_invisible_characters_visible`](`crate::fmt::Formatter::set_invisible_characters_visible`)),
+/// keeping highlights aligned with the placeholders.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct VisibleMetrics<M = DefaultMetrics> {
+	inner: M,
+}
+
+impl<M> VisibleMetrics<M> {
+	/// Wrap the given metrics.
+	pub const fn new(inner: M) -> Self { Self { inner } }
+
+	/// Get the wrapped metrics.
+	pub const fn inner(&self) -> &M { &self.inner }
+}
+
+impl<M: Metrics> Metrics for VisibleMetrics<M> {
+	fn char_width(&self, c: char) -> usize {
+		match visible_placeholder(c) {
+			Some(placeholder) => placeholder.chars().count(),
+			None => self.inner.char_width(c),
+		}
+	}
+
+	fn tab_stop(&self) -> usize { self.inner.tab_stop() }
+
+	fn line_breaks(&self) -> LineBreaks { self.inner.line_breaks() }
+}
+
+/// Returns the visible placeholder of an invisible or control character.
+///
+/// C0 control characters are replaced by their Unicode control picture
+/// (`␀` for `NUL`, `␍` for `CR`, etc.), `DEL` by `␡` and other invisible
+/// characters (C1 control characters, zero-width and bidirectional formatting
+/// characters, byte order mark, etc.) by their code point (`<U+200B>`).
+///
+/// New lines (`\n`) and tabulations (`\t`) have no placeholder, as well as any
+/// visible character.
+#[must_use]
+pub fn visible_placeholder(c: char) -> Option<String> {
+	match c {
+		'\n' | '\t' => None,
+		'\u{0}'..='\u{1f}' => std::char::from_u32(0x2400 + c as u32).map(|c| c.to_string()),
+		'\u{7f}' => Some("\u{2421}".to_string()),
+		'\u{80}'..='\u{9f}'
+		| '\u{ad}'
+		| '\u{34f}'
+		| '\u{61c}'
+		| '\u{115f}'
+		| '\u{1160}'
+		| '\u{180e}'
+		| '\u{200b}'..='\u{200f}'
+		| '\u{2028}'..='\u{202e}'
+		| '\u{2060}'..='\u{2064}'
+		| '\u{2066}'..='\u{2069}'
+		| '\u{feff}'
+		| '\u{fff9}'..='\u{fffb}' => Some(format!("<U+{:04X}>", c as u32)),
+		_ => None,
+	}
 }
@@ -0,0 +1,310 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::{
+	Position,
+	PositionFormat,
+	PositionStyle,
+	Span
+};
+
+/// Location parsing error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseLocationError {
+	/// The input does not match any supported location syntax.
+	Malformed,
+
+	/// A line is given without column.
+	MissingColumn,
+
+	/// The given text is not a valid line or column number.
+	InvalidIndex(String),
+
+	/// A line or column number is `0`, while numbering starts at `1`.
+	ZeroIndex,
+
+	/// The end of a range is located before its start.
+	ReversedRange,
+
+	/// The location has no file name.
+	MissingFile
+}
+
+impl fmt::Display for ParseLocationError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ParseLocationError::Malformed => write!(f, "malformed location"),
+			ParseLocationError::MissingColumn => write!(f, "missing column number"),
+			ParseLocationError::InvalidIndex(index) => write!(f, "invalid line or column number `{}`", index),
+			ParseLocationError::ZeroIndex => write!(f, "line and column numbers start at 1"),
+			ParseLocationError::ReversedRange => write!(f, "range ends before it starts"),
+			ParseLocationError::MissingFile => write!(f, "missing file name")
+		}
+	}
+}
+
+impl std::error::Error for ParseLocationError {}
+
+/// Parse a line or column number, starting at `1`, or the `[end]` sentinel.
+fn parse_index(str: &str) -> Result<usize, ParseLocationError> {
+	if str == "[end]" {
+		Ok(usize::MAX)
+	} else if !str.is_empty() && str.bytes().all(|b| b.is_ascii_digit()) {
+		match str.parse::<usize>() {
+			Ok(0) => Err(ParseLocationError::ZeroIndex),
+			Ok(index) => Ok(index - 1),
+			Err(_) => Err(ParseLocationError::InvalidIndex(str.to_string()))
+		}
+	} else {
+		Err(ParseLocationError::InvalidIndex(str.to_string()))
+	}
+}
+
+/// Parse a `line{sep}column` position where `sep` is one of the given separators.
+fn parse_position(str: &str, separators: &[char]) -> Result<Position, ParseLocationError> {
+	match str.find(|c| separators.contains(&c)) {
+		Some(i) => Ok(Position::new(parse_index(&str[..i])?, parse_index(&str[(i + 1)..])?)),
+		None => {
+			parse_index(str)?;
+			Err(ParseLocationError::MissingColumn)
+		}
+	}
+}
+
+/// Parse a `line L column C` position.
+fn parse_text_position(str: &str) -> Result<Position, ParseLocationError> {
+	let str = str.strip_prefix("line ").ok_or(ParseLocationError::Malformed)?;
+	match str.split_once(" column ") {
+		Some((line, column)) => Ok(Position::new(parse_index(line)?, parse_index(column)?)),
+		None => {
+			parse_index(str)?;
+			Err(ParseLocationError::MissingColumn)
+		}
+	}
+}
+
+/// Span with the given start and last positions, assuming the last character
+/// is one column wide.
+///
+/// A last column equal to `usize::MAX` (`[end]`) denotes the end of the line.
+fn span_to_last(start: Position, last: Position) -> Span {
+	if last.column == usize::MAX {
+		Span::new(start, Position::new(last.line, usize::MAX - 1), last)
+	} else {
+		Span::new(start, last, last.next_column())
+	}
+}
+
+/// Parses a position from its [`fmt::Debug`] (`L:C`) or [`fmt::Display`]
+/// (`line L column C`) representation.
+///
+/// Lines and columns start at `1`. The `[end]` sentinel is parsed as
+/// `usize::MAX`.
+///
+/// ```
+/// use source_span::Position;
+///
+/// assert_eq!("12:5".parse(), Ok(Position::new(11, 4)));
+/// assert_eq!("line 12 column 5".parse(), Ok(Position::new(11, 4)));
+/// assert_eq!("[end]:[end]".parse(), Ok(Position::end()));
+/// ```
+impl FromStr for Position {
+	type Err = ParseLocationError;
+
+	fn from_str(str: &str) -> Result<Self, Self::Err> {
+		if str.starts_with("line ") {
+			parse_text_position(str)
+		} else {
+			parse_position(str, &[':'])
+		}
+	}
+}
+
+/// Parses a span from a range or from its [`fmt::Display`] representation.
+///
+/// The following syntaxes are supported, where positions are given as `L:C`
+/// or `L.C` (GNU style), with lines and columns starting at `1`:
+///
+///  * `L:C-L:C` or `L:C-C` (when the range is on a single line), where the
+///    second position is the [`last`](Span::last) position of the span.
+///  * `L:C`, the span of a single column.
+///  * `from L:C to L:C`, where the second position is the
+///    [`end`](Span::end) of the span, as produced by the [`fmt::Display`]
+///    implementation.
+///  * `line L column C`, the span of a single column.
+///
+/// Since characters metrics are unknown, the end of a span (or its last
+/// position in the `from` syntax) is computed assuming the last character is
+/// one column wide. In particular, an empty span is displayed as a position
+/// and parsed back as a single column span.
+///
+/// ```
+/// use source_span::{Position, Span};
+///
+/// let span: Span = "3:1-4:7".parse().unwrap();
+/// assert_eq!(span, Span::new(Position::new(2, 0), Position::new(3, 6), Position::new(3, 7)));
+///
+/// let span: Span = "3.1-7".parse().unwrap();
+/// assert_eq!(span, Span::new(Position::new(2, 0), Position::new(2, 6), Position::new(2, 7)));
+/// ```
+impl FromStr for Span {
+	type Err = ParseLocationError;
+
+	fn from_str(str: &str) -> Result<Self, Self::Err> {
+		if let Some(range) = str.strip_prefix("from ") {
+			let (start, end) = range.split_once(" to ").ok_or(ParseLocationError::Malformed)?;
+			let start: Position = start.parse()?;
+			let end: Position = end.parse()?;
+
+			if end < start {
+				return Err(ParseLocationError::ReversedRange)
+			}
+
+			return Ok(Span::until(start, end))
+		}
+
+		if str.starts_with("line ") {
+			let pos = parse_text_position(str)?;
+			return Ok(span_to_last(pos, pos))
+		}
+
+		let (start, last) = match str.split_once('-') {
+			Some((start, last)) => (start, Some(last)),
+			None => (str, None)
+		};
+
+		let start = parse_position(start, &[':', '.'])?;
+		let last = match last {
+			Some(last) if last.contains([':', '.']) => parse_position(last, &[':', '.'])?,
+			Some(column) => Position::new(start.line, parse_index(column)?),
+			None => start
+		};
+
+		if last < start {
+			return Err(ParseLocationError::ReversedRange)
+		}
+
+		Ok(span_to_last(start, last))
+	}
+}
+
+/// Span in a named file.
+///
+/// Parses and displays GNU-style `file:L:C[-L:C]` locations.
+/// The span is parsed using the [`Span`] range syntax, so
+/// `file:L.C-L.C` and `file:L.C-C` are also accepted.
+/// The file name may itself contain `:` (such as Windows paths).
+///
+/// ```
+/// use source_span::{Location, Position, Span};
+///
+/// let loc: Location = "src/main.rs:3:1-4:7".parse().unwrap();
+/// assert_eq!(loc.file, "src/main.rs");
+/// assert_eq!(loc.span, Span::new(Position::new(2, 0), Position::new(3, 6), Position::new(3, 7)));
+/// assert_eq!(loc.to_string(), "src/main.rs:3:1-4:7");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Location {
+	/// File name.
+	pub file: String,
+
+	/// Span in the file.
+	pub span: Span
+}
+
+impl Location {
+	/// Create a new location.
+	#[must_use]
+	pub const fn new(file: String, span: Span) -> Self {
+		Self { file, span }
+	}
+}
+
+impl FromStr for Location {
+	type Err = ParseLocationError;
+
+	fn from_str(str: &str) -> Result<Self, Self::Err> {
+		let mut error = None;
+		for (i, _) in str.match_indices(':') {
+			let span = &str[(i + 1)..];
+			if span.starts_with(|c: char| c.is_ascii_digit() || c == '[') {
+				match span.parse() {
+					Ok(span) if i > 0 => return Ok(Location::new(str[..i].to_string(), span)),
+					Ok(_) => return Err(ParseLocationError::MissingFile),
+					Err(e) => {
+						if error.is_none() {
+							error = Some(e)
+						}
+					}
+				}
+			}
+		}
+
+		Err(error.unwrap_or(ParseLocationError::Malformed))
+	}
+}
+
+impl fmt::Display for Location {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let format = PositionFormat::new(1, PositionStyle::Colon).with_file(&self.file);
+		self.span.display_with(format).fmt(f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn span(a: (usize, usize), b: (usize, usize), c: (usize, usize)) -> Span {
+		Span::new(Position::new(a.0, a.1), Position::new(b.0, b.1), Position::new(c.0, c.1))
+	}
+
+	#[test]
+	fn parse_position() {
+		for pos in &[Position::new(0, 0), Position::new(11, 4), Position::new(usize::MAX, 3), Position::end()] {
+			assert_eq!(format!("{:?}", pos).parse(), Ok(*pos));
+			assert_eq!(pos.to_string().parse(), Ok(*pos));
+		}
+
+		assert_eq!("12".parse::<Position>(), Err(ParseLocationError::MissingColumn));
+		assert_eq!("0:1".parse::<Position>(), Err(ParseLocationError::ZeroIndex));
+		assert_eq!("1:x".parse::<Position>(), Err(ParseLocationError::InvalidIndex("x".to_string())));
+		assert_eq!("1:-1".parse::<Position>(), Err(ParseLocationError::InvalidIndex("-1".to_string())));
+		assert_eq!("line 1".parse::<Position>(), Err(ParseLocationError::MissingColumn));
+	}
+
+	#[test]
+	fn parse_span() {
+		assert_eq!("3:1-4:7".parse(), Ok(span((2, 0), (3, 6), (3, 7))));
+		assert_eq!("3.1-4.7".parse(), Ok(span((2, 0), (3, 6), (3, 7))));
+		assert_eq!("3.1-7".parse(), Ok(span((2, 0), (2, 6), (2, 7))));
+		assert_eq!("3:1".parse(), Ok(span((2, 0), (2, 0), (2, 1))));
+		assert_eq!("3:1-[end]:[end]".parse(), Ok(span((2, 0), (usize::MAX, usize::MAX - 1), (usize::MAX, usize::MAX))));
+		assert_eq!("4:7-3:1".parse::<Span>(), Err(ParseLocationError::ReversedRange));
+		assert_eq!("3:1-".parse::<Span>(), Err(ParseLocationError::InvalidIndex("".to_string())));
+
+		let spans = [
+			span((0, 4), (2, 0), (2, 1)),
+			span((1, 4), (1, 4), (1, 5)),
+			span((1, 4), (2, usize::MAX - 1), (3, 0)),
+			span((1, 4), (usize::MAX, usize::MAX - 1), (usize::MAX, usize::MAX))
+		];
+		for span in &spans {
+			assert_eq!(span.to_string().parse(), Ok(*span));
+		}
+	}
+
+	#[test]
+	fn parse_location() {
+		let loc: Location = "file.rs:3:1-4:7".parse().unwrap();
+		assert_eq!(loc, Location::new("file.rs".to_string(), span((2, 0), (3, 6), (3, 7))));
+		assert_eq!(loc.to_string(), "file.rs:3:1-4:7");
+
+		let loc: Location = "C:\\src\\file.rs:3:1".parse().unwrap();
+		assert_eq!(loc, Location::new("C:\\src\\file.rs".to_string(), span((2, 0), (2, 0), (2, 1))));
+		assert_eq!(loc.to_string(), "C:\\src\\file.rs:3:1");
+
+		assert_eq!("file.rs:3:x".parse::<Location>(), Err(ParseLocationError::InvalidIndex("x".to_string())));
+		assert_eq!("file.rs".parse::<Location>(), Err(ParseLocationError::Malformed));
+		assert_eq!(":3:1".parse::<Location>(), Err(ParseLocationError::MissingFile));
+	}
+}
@@ -0,0 +1,310 @@
+use std::fmt;
+use crate::{
+	Position,
+	Span,
+	Metrics,
+	Layout
+};
+
+/// Text patch.
+///
+/// A patch is a list of replacements, each defined by a [`Span`] of the
+/// original text and the string to put in its place.
+/// It can be applied to a string slice, given its [`Layout`], using the
+/// [`apply`](Patch::apply) method.
+///
+/// ```
+/// use source_span::{DEFAULT_METRICS, Layout, Patch, Position, Span};
+///
+/// let str = "let x = 1\nlet y = 2";
+/// let layout = Layout::from(str.chars(), DEFAULT_METRICS);
+///
+/// let mut patch = Patch::new();
+/// patch.add(Span::from(Position::new(0, 9)), ";".to_string());
+/// patch.add(Span::new(Position::new(1, 4), Position::new(1, 4), Position::new(1, 5)), "z".to_string());
+///
+/// let patched = patch.apply(str, &layout).unwrap();
+/// assert_eq!(patched.as_str(), "let x = 1;\nlet z = 2");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Patch {
+	edits: Vec<Edit>
+}
+
+/// Single replacement in a [`Patch`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Edit {
+	span: Span,
+	replacement: String
+}
+
+impl Patch {
+	/// Create a new empty patch.
+	#[must_use]
+	pub fn new() -> Patch {
+		Self::default()
+	}
+
+	/// Checks if the patch contains no replacement.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.edits.is_empty()
+	}
+
+	/// Add a replacement to the patch.
+	///
+	/// The text in the given span will be replaced by the `replacement` string.
+	/// If the span is empty, the string is inserted at the span position.
+	/// Insertions at the same position are applied in the order they are added,
+	/// and before the replacement of a span starting at this position.
+	pub fn add(&mut self, span: Span, replacement: String) {
+		self.edits.push(Edit { span, replacement })
+	}
+
+	/// Apply the patch to the given string slice.
+	///
+	/// It is assumed that the input string slice matches the layout.
+	/// Returns an error if two replacements overlap or if a replacement span is
+	/// not in the bounds of the text.
+	pub fn apply<M: Metrics>(&self, str: &str, layout: &Layout<M>) -> Result<Patched, PatchError> {
+		let mut edits: Vec<(&Edit, usize, usize)> = Vec::with_capacity(self.edits.len());
+		for edit in &self.edits {
+			let start = layout.byte_index_or_end(str, edit.span.start()).ok_or(PatchError::OutOfBounds(edit.span))?;
+			let end = layout.byte_index_or_end(str, edit.span.end()).ok_or(PatchError::OutOfBounds(edit.span))?;
+			edits.push((edit, start, end))
+		}
+
+		// insertions are applied before the replacement starting at the same
+		// position, and the sort is stable, so insertions at the same position
+		// keep their order.
+		edits.sort_by_key(|(_, start, end)| (*start, start != end));
+
+		let mut text = String::with_capacity(str.len());
+		let mut applied: Vec<AppliedEdit> = Vec::with_capacity(edits.len());
+		let mut offset = 0;
+		for (i, (edit, start, end)) in edits.iter().enumerate() {
+			if i > 0 && *start < offset {
+				return Err(PatchError::Overlap(edits[i - 1].0.span, edit.span))
+			}
+
+			text.push_str(&str[offset..*start]);
+			text.push_str(&edit.replacement);
+			offset = *end;
+
+			let mut new_span: Span = shift(&applied, edit.span.start()).into();
+			for c in edit.replacement.chars() {
+				new_span.push(c, layout.metrics())
+			}
+
+			applied.push(AppliedEdit {
+				old: edit.span,
+				new: new_span
+			})
+		}
+
+		text.push_str(&str[offset..]);
+
+		Ok(Patched {
+			text,
+			edits: applied
+		})
+	}
+}
+
+/// Replacement applied by a [`Patch`].
+#[derive(Clone, Copy, Debug)]
+struct AppliedEdit {
+	/// Replaced span in the original text.
+	old: Span,
+
+	/// Span of the replacement in the patched text.
+	new: Span
+}
+
+/// Compute where a position of the original text lands in the patched text,
+/// given the edits applied before it.
+fn shift(edits: &[AppliedEdit], pos: Position) -> Position {
+	match edits.iter().rev().find(|e| e.old.end() <= pos) {
+		Some(e) => {
+			if pos.line == e.old.end().line {
+				Position::new(e.new.end().line, e.new.end().column + pos.column - e.old.end().column)
+			} else {
+				Position::new(pos.line - e.old.end().line + e.new.end().line, pos.column)
+			}
+		},
+		None => pos
+	}
+}
+
+/// Patched text.
+///
+/// This is the result of the [`Patch::apply`] function.
+/// It holds the new text and can map positions of the original text to
+/// positions in the new text.
+#[derive(Clone, Debug)]
+pub struct Patched {
+	text: String,
+	edits: Vec<AppliedEdit>
+}
+
+impl Patched {
+	/// Get the patched text.
+	#[must_use]
+	pub fn as_str(&self) -> &str {
+		&self.text
+	}
+
+	/// Get the patched text as a `String`.
+	#[must_use]
+	pub fn into_string(self) -> String {
+		self.text
+	}
+
+	/// Map a position of the original text to the matching position in the
+	/// patched text.
+	///
+	/// Returns `None` if the position was part of a replaced span.
+	/// Positions following an insertion are moved after the inserted text.
+	///
+	/// Note that columns following a replacement on the same line are shifted
+	/// by the width of the replacement, without taking tab stops into account.
+	#[must_use]
+	pub fn map_position(&self, pos: Position) -> Option<Position> {
+		if self.edits.iter().any(|e| e.old.start() <= pos && pos < e.old.end()) {
+			None
+		} else {
+			Some(shift(&self.edits, pos))
+		}
+	}
+
+	/// Returns an iterator over the spans of the replacements in the patched
+	/// text, in position order.
+	pub fn replaced_spans(&self) -> impl '_ + Iterator<Item = Span> {
+		self.edits.iter().map(|e| e.new)
+	}
+}
+
+impl fmt::Display for Patched {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.text.fmt(f)
+	}
+}
+
+/// Patch application error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchError {
+	/// The two given replacement spans overlap.
+	Overlap(Span, Span),
+
+	/// The given replacement span is not in the bounds of the text.
+	OutOfBounds(Span)
+}
+
+impl fmt::Display for PatchError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			PatchError::Overlap(a, b) => write!(f, "overlapping replacements {} and {}", a, b),
+			PatchError::OutOfBounds(span) => write!(f, "replacement {} is out of bounds", span)
+		}
+	}
+}
+
+impl std::error::Error for PatchError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn span(a: (usize, usize), b: (usize, usize), c: (usize, usize)) -> Span {
+		Span::new(Position::new(a.0, a.1), Position::new(b.0, b.1), Position::new(c.0, c.1))
+	}
+
+	#[test]
+	fn apply_replacements() {
+		let str = "Hel\nlo\nWorld!";
+		let layout = Layout::from(str.chars(), crate::DEFAULT_METRICS);
+
+		let mut patch = Patch::new();
+		patch.add(span((2, 0), (2, 4), (2, 5)), "Earth".to_string());
+		patch.add(span((0, 0), (0, 2), (0, 3)), "Ha".to_string());
+
+		let patched = patch.apply(str, &layout).unwrap();
+		assert_eq!(patched.as_str(), "Ha\nlo\nEarth!");
+	}
+
+	#[test]
+	fn apply_insertions() {
+		let str = "Hello";
+		let layout = Layout::from(str.chars(), crate::DEFAULT_METRICS);
+
+		let mut patch = Patch::new();
+		patch.add(Position::new(0, 5).into(), "!".to_string());
+		patch.add(Position::new(0, 5).into(), "?".to_string());
+		patch.add(Position::new(0, 0).into(), "> ".to_string());
+
+		let patched = patch.apply(str, &layout).unwrap();
+		assert_eq!(patched.as_str(), "> Hello!?");
+	}
+
+	#[test]
+	fn apply_insertion_before_replacement() {
+		let str = "Hello World!";
+		let layout = Layout::from(str.chars(), crate::DEFAULT_METRICS);
+		let insertion = Span::from(Position::new(0, 6));
+		let replacement = span((0, 6), (0, 10), (0, 11));
+
+		let mut patch = Patch::new();
+		patch.add(insertion, "big ".to_string());
+		patch.add(replacement, "Earth".to_string());
+		assert_eq!(patch.apply(str, &layout).unwrap().as_str(), "Hello big Earth!");
+
+		let mut patch = Patch::new();
+		patch.add(replacement, "Earth".to_string());
+		patch.add(insertion, "big ".to_string());
+		assert_eq!(patch.apply(str, &layout).unwrap().as_str(), "Hello big Earth!");
+	}
+
+	#[test]
+	fn apply_overlapping() {
+		let str = "Hello World!";
+		let layout = Layout::from(str.chars(), crate::DEFAULT_METRICS);
+
+		let mut patch = Patch::new();
+		patch.add(span((0, 0), (0, 4), (0, 5)), "Bye".to_string());
+		patch.add(span((0, 3), (0, 7), (0, 8)), "".to_string());
+
+		assert!(matches!(patch.apply(str, &layout), Err(PatchError::Overlap(_, _))));
+	}
+
+	#[test]
+	fn apply_out_of_bounds() {
+		let str = "Hello";
+		let layout = Layout::from(str.chars(), crate::DEFAULT_METRICS);
+
+		let mut patch = Patch::new();
+		patch.add(span((1, 0), (1, 0), (1, 1)), "".to_string());
+
+		assert!(matches!(patch.apply(str, &layout), Err(PatchError::OutOfBounds(_))));
+	}
+
+	#[test]
+	fn map_positions() {
+		let str = "Hel\nlo\nWorld!";
+		let layout = Layout::from(str.chars(), crate::DEFAULT_METRICS);
+
+		let mut patch = Patch::new();
+		patch.add(span((0, 1), (1, 0), (1, 1)), "ELL\nL".to_string());
+		patch.add(span((2, 0), (2, 4), (2, 5)), "Earth".to_string());
+
+		let patched = patch.apply(str, &layout).unwrap();
+		assert_eq!(patched.as_str(), "HELL\nLo\nEarth!");
+		assert_eq!(patched.map_position(Position::new(0, 0)), Some(Position::new(0, 0)));
+		assert_eq!(patched.map_position(Position::new(0, 2)), None);
+		assert_eq!(patched.map_position(Position::new(1, 1)), Some(Position::new(1, 1)));
+		assert_eq!(patched.map_position(Position::new(2, 5)), Some(Position::new(2, 5)));
+
+		let spans: Vec<_> = patched.replaced_spans().collect();
+		assert_eq!(spans[0], span((0, 1), (1, 0), (1, 1)));
+		assert_eq!(spans[1], span((2, 0), (2, 4), (2, 5)));
+	}
+}
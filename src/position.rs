@@ -1,4 +1,4 @@
-use crate::{Metrics, Span};
+use crate::{DisplayPosition, Metrics, PositionFormat, Span};
 use std::fmt;
 
 /// Position in a source file (line and column).
@@ -18,6 +18,8 @@ use std::fmt;
 ///
 /// Both of them will display lines and columns starting at `1` even though the
 /// internal representation starts at `0`.
+/// Other formats are available through the
+/// [`display_with`](Position::display_with) method.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Default, PartialOrd, Ord)]
 pub struct Position {
 	/// Line number, starting at `0`.
@@ -43,23 +45,25 @@ impl Position {
 	///
 	/// assert_eq!(
 	/// 	Position::end(),
-	/// 	Position::new(usize::max_value(), usize::max_value())
+	/// 	Position::new(usize::MAX, usize::MAX)
 	/// 	);
 	/// ```
 	#[must_use]
 	pub const fn end() -> Self {
 		Self {
-			line: usize::max_value(),
-			column: usize::max_value(),
+			line: usize::MAX,
+			column: usize::MAX,
 		}
 	}
 
 	/// Move to the next column.
+	///
+	/// The column saturates at `usize::MAX`.
 	#[must_use]
 	pub const fn next_column(&self) -> Self {
 		Self {
 			line: self.line,
-			column: self.column + 1,
+			column: self.column.saturating_add(1),
 		}
 	}
 
@@ -73,10 +77,12 @@ impl Position {
 	}
 
 	/// Move to the next line, and reset the column position.
+	///
+	/// The line saturates at `usize::MAX`.
 	#[must_use]
 	pub const fn next_line(&self) -> Self {
 		Self {
-			line: self.line + 1,
+			line: self.line.saturating_add(1),
 			column: 0,
 		}
 	}
@@ -87,14 +93,21 @@ impl Position {
 	///
 	/// This crate is intended to help with incremental lexing/parsing.
 	/// Therefore, any control character moving the cursor backward will be
-	/// ignored: it will be treated as a 0-width character with no
-	/// semantics.
+	/// ignored: it will be treated as a character with no semantics, whose
+	/// width is given by the metrics (`0` with the
+	/// [`DefaultMetrics`](`crate::DefaultMetrics`)).
 	///
 	/// ### New lines
 	///
-	/// The `\n` character is interpreted with the Unix semantics, as the new
-	/// line (NL) character. It will reset the column position to `0` and
-	/// move to the next line.
+	/// Line breaks are given by the [`Metrics::line_breaks`] policy.
+	/// By default, the `\n` character is interpreted with the Unix semantics,
+	/// as the new line (NL) character. A line break will reset the column
+	/// position to `0` and move to the next line.
+	///
+	/// Note that this function does not know the previous character, and hence
+	/// cannot handle line break sequences such as `\r\n` with the
+	/// [`LineBreaks::Unicode`](`crate::LineBreaks::Unicode`) policy.
+	/// Use [`next_after`](Position::next_after) in that case.
 	///
 	/// ### Tabulations
 	///
@@ -108,22 +121,26 @@ impl Position {
 	/// They will move the cursor by only one column as any other
 	/// regular-width character. You are welcome to contribute to handle
 	/// them.
+	///
+	/// ## Overflow
+	///
+	/// Lines and columns saturate at `usize::MAX`.
 	#[must_use]
 	pub fn next<M: Metrics>(&self, c: char, metrics: &M) -> Self {
 		match c {
-			'\n' => self.next_line(),
+			c if metrics.line_breaks().is_line_break(c) => self.next_line(),
 			'\t' => {
-				let ts = metrics.tab_stop();
+				// a tab stop of 0 is treated as 1.
+				let ts = std::cmp::max(metrics.tab_stop(), 1);
 				Self {
 					line: self.line,
-					column: (self.column / ts) * ts + ts,
+					column: (self.column / ts).saturating_mul(ts).saturating_add(ts),
 				}
 			}
-			c if c.is_control() => *self,
 			_ => {
 				Self {
 					line: self.line,
-					column: self.column + metrics.char_width(c),
+					column: self.column.saturating_add(metrics.char_width(c)),
 				}
 			}
 		}
@@ -131,6 +148,21 @@ impl Position {
 
 	pub fn shift<M: Metrics>(&mut self, c: char, metrics: &M) { *self = self.next(c, metrics) }
 
+	/// Move to the position following the given [`char`], directly following
+	/// the `previous` character, using the given [`Metrics`].
+	///
+	/// This is the same as [`next`](Position::next), except that line break
+	/// sequences spanning multiple characters (such as `\r\n` with the
+	/// [`LineBreaks::Unicode`](`crate::LineBreaks::Unicode`) policy) count as
+	/// a single line break.
+	#[must_use]
+	pub fn next_after<M: Metrics>(&self, previous: Option<char>, c: char, metrics: &M) -> Self {
+		match previous {
+			Some(previous) if metrics.line_breaks().continues(previous, c) => *self,
+			_ => self.next(c, metrics),
+		}
+	}
+
 	/// Creates the span ending at this position (excluded) from
 	/// `first` included to `last` included.
 	/// 
@@ -163,15 +195,28 @@ impl Position {
 	pub fn to(&self, last: Self, end: Self) -> Span {
 		Span::new(*self, last, end)
 	}
+
+	/// Returns a value displaying this position with the given format.
+	///
+	/// ```
+	/// use source_span::{Position, PositionFormat, PositionStyle};
+	///
+	/// let format = PositionFormat::new(0, PositionStyle::Colon).with_file("main.rs");
+	/// assert_eq!(Position::new(2, 3).display_with(format).to_string(), "main.rs:2:3");
+	/// ```
+	#[must_use]
+	pub const fn display_with<'a>(self, format: PositionFormat<'a>) -> DisplayPosition<'a> {
+		DisplayPosition::new(self, format)
+	}
 }
 
 impl fmt::Display for Position {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		if self.line == usize::max_value() && self.column == usize::max_value() {
+		if self.line == usize::MAX && self.column == usize::MAX {
 			write!(f, "line [end] column [end]")
-		} else if self.line == usize::max_value() {
+		} else if self.line == usize::MAX {
 			write!(f, "line [end] column {}", self.column + 1)
-		} else if self.column == usize::max_value() {
+		} else if self.column == usize::MAX {
 			write!(f, "line {} column [end]", self.line + 1)
 		} else {
 			write!(f, "line {} column {}", self.line + 1, self.column + 1)
@@ -181,11 +226,11 @@ impl fmt::Display for Position {
 
 impl fmt::Debug for Position {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		if self.line == usize::max_value() && self.column == usize::max_value() {
+		if self.line == usize::MAX && self.column == usize::MAX {
 			write!(f, "[end]:[end]")
-		} else if self.line == usize::max_value() {
+		} else if self.line == usize::MAX {
 			write!(f, "[end]:{}", self.column + 1)
-		} else if self.column == usize::max_value() {
+		} else if self.column == usize::MAX {
 			write!(f, "{}:[end]", self.line + 1)
 		} else {
 			write!(f, "{}:{}", self.line + 1, self.column + 1)
@@ -244,19 +289,45 @@ mod tests {
 	fn test_debug() {
 		assert_eq!(format!("{:?}", Position::new(2, 3)), "3:4".to_string());
 		assert_eq!(
-			format!("{:?}", Position::new(usize::max_value(), 3)),
+			format!("{:?}", Position::new(usize::MAX, 3)),
 			"[end]:4".to_string()
 		);
 		assert_eq!(
-			format!("{:?}", Position::new(3, usize::max_value())),
+			format!("{:?}", Position::new(3, usize::MAX)),
 			"4:[end]".to_string()
 		);
 		assert_eq!(
 			format!(
 				"{:?}",
-				Position::new(usize::max_value(), usize::max_value())
+				Position::new(usize::MAX, usize::MAX)
 			),
 			"[end]:[end]".to_string()
 		);
 	}
+
+	#[test]
+	fn test_next_control() {
+		let pos = Position::new(0, 4);
+		assert_eq!(pos.next('\r', &crate::DEFAULT_METRICS), pos);
+		assert_eq!(pos.next('\u{0}', &crate::DEFAULT_METRICS), pos);
+
+		let metrics = crate::VisibleMetrics::new(crate::DEFAULT_METRICS);
+		assert_eq!(pos.next('\r', &metrics), Position::new(0, 5));
+		assert_eq!(pos.next('\u{feff}', &metrics), Position::new(0, 12));
+		assert_eq!(pos.next('a', &metrics), Position::new(0, 5));
+	}
+
+	#[test]
+	fn test_next_after() {
+		let pos = Position::new(0, 4);
+		let unix = crate::DEFAULT_METRICS;
+		let unicode = crate::DefaultMetrics::with_line_breaks(crate::LineBreaks::Unicode);
+
+		assert_eq!(pos.next('\r', &unicode), Position::new(1, 0));
+		assert_eq!(pos.next('\u{2028}', &unicode), Position::new(1, 0));
+		assert_eq!(pos.next('\u{2028}', &unix), Position::new(0, 5));
+		assert_eq!(pos.next_after(Some('\r'), '\n', &unicode), pos);
+		assert_eq!(pos.next_after(Some('\r'), '\n', &unix), Position::new(1, 0));
+		assert_eq!(pos.next_after(Some('\n'), '\n', &unicode), Position::new(1, 0));
+	}
 }
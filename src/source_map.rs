@@ -0,0 +1,303 @@
+use std::{convert::TryFrom, fmt};
+use crate::{
+	Layout,
+	Location,
+	Metrics,
+	Span
+};
+
+/// Identifier of a file in a [`SourceMap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct FileId(u32);
+
+impl FileId {
+	/// Returns the index of the file in its source map.
+	#[must_use]
+	pub const fn index(&self) -> usize {
+		self.0 as usize
+	}
+}
+
+/// Compact span.
+///
+/// Stores the file and the range of byte offsets of a span in 12 bytes,
+/// instead of the 48 bytes (on 64-bit platforms) of a [`Span`].
+/// It can be converted back to a [`Span`] using the [`SourceMap`] defining
+/// the file, with [`SourceMap::span`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct CompactSpan {
+	/// File of the span.
+	file: FileId,
+
+	/// Byte offset of the first character in the span.
+	start: u32,
+
+	/// Byte offset of the character directly following the span.
+	end: u32
+}
+
+impl CompactSpan {
+	/// Create a new compact span from a file and a range of byte offsets.
+	///
+	/// If `end` is lower than `start`, the span is the empty span at `start`.
+	#[must_use]
+	pub fn new(file: FileId, start: u32, end: u32) -> Self {
+		Self {
+			file,
+			start,
+			end: std::cmp::max(start, end)
+		}
+	}
+
+	/// Returns the file of the span.
+	#[must_use]
+	pub const fn file(&self) -> FileId {
+		self.file
+	}
+
+	/// Returns the byte offset of the first character in the span.
+	#[must_use]
+	pub const fn start(&self) -> u32 {
+		self.start
+	}
+
+	/// Returns the byte offset of the character directly following the span.
+	#[must_use]
+	pub const fn end(&self) -> u32 {
+		self.end
+	}
+
+	/// Returns the length of the span in bytes.
+	#[must_use]
+	pub const fn len(&self) -> u32 {
+		self.end - self.start
+	}
+
+	/// Checks if the span is empty.
+	#[must_use]
+	pub const fn is_empty(&self) -> bool {
+		self.start == self.end
+	}
+}
+
+/// File of a [`SourceMap`].
+struct SourceFile<M: Metrics> {
+	name: String,
+	text: String,
+	layout: Layout<M>
+}
+
+/// Source map.
+///
+/// Stores the content of source files, so that [`CompactSpan`] can be
+/// converted from and to [`Span`].
+///
+/// ```
+/// use source_span::{DEFAULT_METRICS, Position, SourceMap, Span};
+///
+/// let mut source_map = SourceMap::new(DEFAULT_METRICS);
+/// let file = source_map.add("main.rs".to_string(), "fn main() {\n\tprintln!(\"Hello\")\n}\n".to_string());
+///
+/// let span = Span::new(Position::new(1, 8), Position::new(1, 15), Position::new(1, 16));
+/// let compact = source_map.compact(file, span).unwrap();
+///
+/// assert_eq!(std::mem::size_of_val(&compact), 12);
+/// assert_eq!(source_map.slice(compact), Some("println!"));
+/// assert_eq!(source_map.span(compact), Some(span));
+/// ```
+pub struct SourceMap<M: Metrics> {
+	files: Vec<SourceFile<M>>,
+
+	/// Metrics used for every file.
+	metrics: M
+}
+
+impl<M: Metrics + Clone> SourceMap<M> {
+	/// Create a new empty source map, using the given metrics for every file.
+	pub fn new(metrics: M) -> Self {
+		Self {
+			files: Vec::new(),
+			metrics
+		}
+	}
+
+	/// Add a file to the source map and returns its identifier.
+	///
+	/// # Panics
+	///
+	/// Panics if the map already contains 2<sup>32</sup> files, or if the text is
+	/// longer than `u32::MAX` bytes. See [`try_add`](SourceMap::try_add) for
+	/// a non-panicking version.
+	pub fn add(&mut self, name: String, text: String) -> FileId {
+		match self.try_add(name, text) {
+			Ok(id) => id,
+			Err(e) => panic!("{}", e)
+		}
+	}
+
+	/// Add a file to the source map and returns its identifier.
+	///
+	/// Returns an error if the map already contains 2<sup>32</sup> files, or if
+	/// the text is longer than `u32::MAX` bytes, since they cannot be
+	/// represented by a [`CompactSpan`].
+	pub fn try_add(&mut self, name: String, text: String) -> Result<FileId, SourceMapError> {
+		let id = file_id(self.files.len(), text.len())?;
+		let layout = Layout::from(text.chars(), self.metrics.clone());
+		self.files.push(SourceFile { name, text, layout });
+		Ok(id)
+	}
+}
+
+/// Returns the identifier of a file of `len` bytes added to a source map
+/// containing `count` files.
+fn file_id(count: usize, len: usize) -> Result<FileId, SourceMapError> {
+	if u32::try_from(len).is_err() {
+		return Err(SourceMapError::FileTooLarge(len))
+	}
+
+	u32::try_from(count).map(FileId).map_err(|_| SourceMapError::TooManyFiles)
+}
+
+impl<M: Metrics> SourceMap<M> {
+	/// Returns the number of files in the source map.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.files.len()
+	}
+
+	/// Checks if the source map contains no file.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.files.is_empty()
+	}
+
+	/// Returns the name of the given file.
+	#[must_use]
+	pub fn name(&self, file: FileId) -> Option<&str> {
+		self.files.get(file.index()).map(|f| f.name.as_str())
+	}
+
+	/// Returns the text of the given file.
+	#[must_use]
+	pub fn text(&self, file: FileId) -> Option<&str> {
+		self.files.get(file.index()).map(|f| f.text.as_str())
+	}
+
+	/// Returns the layout of the given file.
+	#[must_use]
+	pub fn layout(&self, file: FileId) -> Option<&Layout<M>> {
+		self.files.get(file.index()).map(|f| &f.layout)
+	}
+
+	/// Returns the compact representation of the given span in the given file.
+	///
+	/// Returns `None` if the file is unknown or the span is not in the bounds
+	/// of the file.
+	#[must_use]
+	pub fn compact(&self, file: FileId, span: Span) -> Option<CompactSpan> {
+		let f = self.files.get(file.index())?;
+		let start = f.layout.byte_index_or_end(&f.text, span.start())?;
+		let end = f.layout.byte_index_or_end(&f.text, span.end())?;
+
+		// files are at most `u32::MAX` bytes long.
+		Some(CompactSpan::new(file, start as u32, end as u32))
+	}
+
+	/// Returns the full span of the given compact span.
+	///
+	/// Returns `None` if the file is unknown or if the compact span is not in
+	/// the bounds of the file or not on UTF8 character boundaries.
+	/// It also returns `None` if the compact span ends with a 0 columns wide
+	/// character, since such a span cannot be represented with positions.
+	#[must_use]
+	pub fn span(&self, span: CompactSpan) -> Option<Span> {
+		let f = self.files.get(span.file.index())?;
+		f.layout.span_of_range(&f.text, span.start as usize, span.end as usize)
+	}
+
+	/// Returns the text of the given compact span.
+	#[must_use]
+	pub fn slice(&self, span: CompactSpan) -> Option<&str> {
+		self.text(span.file)?.get((span.start as usize)..(span.end as usize))
+	}
+
+	/// Returns the location (file name and full span) of the given compact
+	/// span.
+	#[must_use]
+	pub fn location(&self, span: CompactSpan) -> Option<Location> {
+		Some(Location::new(self.name(span.file)?.to_string(), self.span(span)?))
+	}
+}
+
+/// Source map error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceMapError {
+	/// The source map already contains 2<sup>32</sup> files.
+	TooManyFiles,
+
+	/// The file, of the given byte length, is longer than `u32::MAX` bytes.
+	FileTooLarge(usize)
+}
+
+impl fmt::Display for SourceMapError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SourceMapError::TooManyFiles => write!(f, "too many source files"),
+			SourceMapError::FileTooLarge(len) => write!(f, "source file is too large ({} bytes)", len)
+		}
+	}
+}
+
+impl std::error::Error for SourceMapError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{DefaultMetrics, LineBreaks, Position};
+
+	const TEXT: &str = "fn main() {\r\n\tlet x = \"été\";\r\n}\u{2028}";
+
+	#[test]
+	fn size() {
+		assert_eq!(std::mem::size_of::<CompactSpan>(), 12)
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut source_map = SourceMap::new(DefaultMetrics::with_line_breaks(LineBreaks::Unicode));
+		let a = source_map.add("a.rs".to_string(), "".to_string());
+		let b = source_map.add("b.rs".to_string(), TEXT.to_string());
+
+		let mut boundaries: Vec<_> = TEXT.char_indices().map(|(i, _)| i).collect();
+		boundaries.push(TEXT.len());
+		boundaries.retain(|i| !TEXT[*i..].starts_with('\n'));
+
+		for (n, start) in boundaries.iter().enumerate() {
+			for end in &boundaries[n..] {
+				let compact = CompactSpan::new(b, *start as u32, *end as u32);
+				let span = source_map.span(compact).unwrap();
+				assert_eq!(source_map.compact(b, span), Some(compact));
+				assert_eq!(source_map.layout(b).unwrap().span_slice(TEXT, span), &TEXT[*start..*end]);
+			}
+		}
+
+		let crlf = CompactSpan::new(b, 11, 13);
+		assert_eq!(source_map.slice(crlf), Some("\r\n"));
+		assert_eq!(source_map.span(crlf), Some(Span::new(Position::new(0, 11), Position::new(0, 11), Position::new(1, 0))));
+
+		assert_eq!(source_map.span(CompactSpan::new(a, 0, 0)), Some(Position::new(0, 0).into()));
+		assert_eq!(source_map.span(CompactSpan::new(a, 0, 1)), None);
+		assert_eq!(source_map.span(CompactSpan::new(FileId(2), 0, 0)), None);
+		assert_eq!(source_map.location(CompactSpan::new(b, 3, 7)).unwrap().to_string(), "b.rs:1:4-1:7");
+	}
+
+	#[test]
+	#[cfg(target_pointer_width = "64")]
+	fn limits() {
+		let max = u32::MAX as usize;
+		assert_eq!(file_id(max - 1, max), Ok(FileId(u32::MAX - 1)));
+		assert_eq!(file_id(max, 0), Ok(FileId(u32::MAX)));
+		assert_eq!(file_id(max + 1, 0), Err(SourceMapError::TooManyFiles));
+		assert_eq!(file_id(0, max + 1), Err(SourceMapError::FileTooLarge(max + 1)));
+	}
+}
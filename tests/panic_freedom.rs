@@ -0,0 +1,137 @@
+//! Property tests checking that the [`Formatter`] never panics, whatever the
+//! rendered text, span and highlights.
+use proptest::prelude::*;
+use source_span::{
+	fmt::{Formatter, Mode, Style, TabStyle},
+	DefaultMetrics, LineBreaks, Position, Span, VisibleMetrics,
+};
+
+fn text() -> impl Strategy<Value = String> {
+	prop::collection::vec(
+		prop::sample::select(vec![
+			'a', 'b', ' ', '\n', '\r', '\t', '\u{0}', '\u{7f}', '\u{200b}', '\u{2028}', 'é', '字', '🦀',
+		]),
+		0..60,
+	)
+	.prop_map(|chars| chars.into_iter().collect())
+}
+
+fn index() -> impl Strategy<Value = usize> {
+	prop_oneof![
+		8 => 0..12usize,
+		1 => Just(usize::MAX - 1),
+		1 => Just(usize::MAX),
+	]
+}
+
+fn position() -> impl Strategy<Value = Position> {
+	(index(), index()).prop_map(|(line, column)| Position::new(line, column))
+}
+
+fn span() -> impl Strategy<Value = Span> {
+	(position(), position(), position()).prop_filter_map("invalid span", |(a, b, c)| {
+		let mut p = [a, b, c];
+		p.sort();
+		Span::try_new(p[0], p[1], p[2]).ok()
+	})
+}
+
+fn style() -> impl Strategy<Value = Style> {
+	prop::sample::select(vec![Style::Error, Style::Warning, Style::Note, Style::Help])
+}
+
+fn label() -> impl Strategy<Value = Option<String>> {
+	prop::option::of(prop::sample::select(vec!["label", "", "a\nmulti-line\nlabel", "\t\r"]).prop_map(String::from))
+}
+
+fn mode() -> impl Strategy<Value = Mode> {
+	prop::sample::select(vec![Mode::Full, Mode::Short, Mode::Compact])
+}
+
+#[derive(Debug)]
+struct Settings {
+	highlights: Vec<(Span, Option<String>, Style)>,
+	mode: Mode,
+	viewbox: Option<usize>,
+	line_numbers: bool,
+	tab_style: TabStyle,
+	invisible: bool,
+}
+
+impl Settings {
+	fn formatter(&self) -> Formatter {
+		let mut fmt = Formatter::new();
+		fmt.set_mode(self.mode);
+		fmt.set_viewbox(self.viewbox);
+		fmt.set_line_numbers_visible(self.line_numbers);
+		fmt.set_tab_style(self.tab_style);
+		fmt.set_invisible_characters_visible(self.invisible);
+		for (span, label, style) in &self.highlights {
+			fmt.add(*span, label.clone(), *style)
+		}
+
+		fmt
+	}
+}
+
+prop_compose! {
+	fn settings()(
+		highlights in prop::collection::vec((span(), label(), style()), 0..6),
+		mode in mode(),
+		viewbox in prop::option::of(prop_oneof![0..4usize, Just(usize::MAX)]),
+		line_numbers in any::<bool>(),
+		tab_style in prop::sample::select(vec![TabStyle::Spaces, TabStyle::Arrow]),
+		invisible in any::<bool>()
+	) -> Settings {
+		Settings { highlights, mode, viewbox, line_numbers, tab_style, invisible }
+	}
+}
+
+proptest! {
+	#[test]
+	fn render_never_panics(text in text(), settings in settings(), span in prop::option::of(span()), unicode in any::<bool>(), visible in any::<bool>(), tab_stop in prop_oneof![1 => Just(0usize), 4 => 1..9usize]) {
+		let mut metrics = DefaultMetrics::with_line_breaks(if unicode { LineBreaks::Unicode } else { LineBreaks::Unix });
+		metrics.set_tab_stop(tab_stop);
+		let chars = text.chars().map(Ok::<char, ()>);
+		let fmt = settings.formatter();
+
+		if visible {
+			let metrics = VisibleMetrics::new(metrics);
+			let span = span.unwrap_or_else(|| Span::of_string(&text, &metrics));
+			let _ = fmt.margin_len(&span);
+			let _ = fmt.render(chars, span, &metrics).unwrap().to_plain_string();
+		} else {
+			let span = span.unwrap_or_else(|| Span::of_string(&text, &metrics));
+			let _ = fmt.margin_len(&span);
+			let _ = fmt.render(chars, span, &metrics).unwrap().to_string();
+		}
+	}
+}
+
+#[test]
+fn render_huge_positions() {
+	let text = "fn main() {\n\tprintln!(\"Hello\")\n}\n";
+	let span = Span::new(Position::new(0, 0), Position::new(usize::MAX, usize::MAX - 1), Position::end());
+
+	let mut fmt = Formatter::new();
+	fmt.add(span, Some("everything".to_string()), Style::Error);
+	fmt.add(Span::new(Position::new(1, 1), Position::new(1, usize::MAX - 1), Position::new(2, 0)), Some("line".to_string()), Style::Note);
+
+	for mode in &[Mode::Full, Mode::Short, Mode::Compact] {
+		fmt.set_mode(*mode);
+		let _ = fmt.margin_len(&span);
+		let _ = fmt.render(text.chars().map(Ok::<char, ()>), span, &source_span::DEFAULT_METRICS).unwrap().to_string();
+	}
+}
+
+#[test]
+fn render_huge_area() {
+	// a label as high as the maximum char map height, at the end of a long
+	// line.
+	let text = "a".repeat(1 << 13);
+	let span = Span::of_string(&text, &source_span::DEFAULT_METRICS);
+
+	let mut fmt = Formatter::new();
+	fmt.add(span, Some("label\n".repeat(1 << 16)), Style::Error);
+	let _ = fmt.render(text.chars().map(Ok::<char, ()>), span, &source_span::DEFAULT_METRICS).unwrap().to_string();
+}
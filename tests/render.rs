@@ -0,0 +1,276 @@
+//! Snapshot tests of the [`Formatter`] output.
+//!
+//! Each test renders a source text with some highlights and compares the
+//! result (without colors) to the reference output stored in the `snapshots`
+//! directory. Run the tests with the `UPDATE_SNAPSHOTS` environment variable
+//! set to (re)generate the reference outputs.
+use source_span::{
+	fmt::{Formatted, Formatter, Mode, Style, TabStyle},
+	DefaultMetrics, LineBreaks, Metrics, Position, Span, VisibleMetrics, DEFAULT_METRICS,
+};
+use std::path::PathBuf;
+
+fn snapshot_path(name: &str) -> PathBuf {
+	[env!("CARGO_MANIFEST_DIR"), "tests", "snapshots", &format!("{}.txt", name)]
+		.iter()
+		.collect()
+}
+
+fn check(name: &str, formatted: &Formatted) {
+	let output = formatted.to_plain_string();
+	let path = snapshot_path(name);
+
+	if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+		std::fs::write(&path, &output).unwrap()
+	} else {
+		let expected = std::fs::read_to_string(&path)
+			.unwrap_or_else(|_| panic!("missing snapshot `{}`", path.display()));
+		assert!(
+			output == expected,
+			"snapshot `{}` differs.\nexpected:\n{}\nfound:\n{}",
+			name,
+			expected,
+			output
+		)
+	}
+}
+
+fn render(fmt: &Formatter, source: &str) -> Formatted {
+	render_with_metrics(fmt, source, &DEFAULT_METRICS)
+}
+
+fn render_with_metrics<M: Metrics>(fmt: &Formatter, source: &str, metrics: &M) -> Formatted {
+	let span = Span::of_string(source, metrics);
+	fmt.render(source.chars().map(Ok::<char, ()>), span, metrics)
+		.unwrap()
+}
+
+fn span(start: (usize, usize), last: (usize, usize), end: (usize, usize)) -> Span {
+	Span::new(
+		Position::new(start.0, start.1),
+		Position::new(last.0, last.1),
+		Position::new(end.0, end.1),
+	)
+}
+
+/// Span of the characters from `start` to `last` (included) on a single line.
+fn line_span(line: usize, start: usize, last: usize) -> Span {
+	span((line, start), (line, last), (line, last + 1))
+}
+
+const HELLO: &str = "fn main() {\n    println!(\"Hello World!\")\n}\n";
+
+#[test]
+fn multi_line() {
+	let mut fmt = Formatter::new();
+	fmt.add(span((0, 10), (2, 0), (2, 1)), Some("a block".to_string()), Style::Error);
+	fmt.add(line_span(1, 13, 26), Some("a string".to_string()), Style::Note);
+	check("multi_line", &render(&fmt, HELLO))
+}
+
+#[test]
+fn no_line_numbers() {
+	let mut fmt = Formatter::new();
+	fmt.hide_line_numbers();
+	fmt.add(line_span(1, 13, 26), Some("a string".to_string()), Style::Note);
+	check("no_line_numbers", &render(&fmt, HELLO))
+}
+
+#[test]
+fn nested() {
+	let source = "let x = ((a + b) * (c - d));\n";
+	let mut fmt = Formatter::new();
+	fmt.add(line_span(0, 8, 26), Some("outer".to_string()), Style::Error);
+	fmt.add(line_span(0, 9, 15), Some("left".to_string()), Style::Note);
+	fmt.add(line_span(0, 19, 25), Some("right".to_string()), Style::Help);
+	fmt.add(line_span(0, 10, 14), None, Style::Warning);
+	check("nested", &render(&fmt, source))
+}
+
+#[test]
+fn overlapping() {
+	let source = "if a {\n    b(c, d)\n} else {\n    e\n}\n";
+	let mut fmt = Formatter::new();
+	fmt.add(line_span(1, 4, 8), Some("first".to_string()), Style::Note);
+	fmt.add(line_span(1, 6, 10), Some("second".to_string()), Style::Warning);
+	fmt.add(span((0, 5), (2, 0), (2, 1)), Some("then".to_string()), Style::Help);
+	fmt.add(span((1, 4), (4, 0), (4, 1)), Some("across".to_string()), Style::Error);
+	check("overlapping", &render(&fmt, source))
+}
+
+#[test]
+fn label_texts_do_not_overlap() {
+	let source = "let result = compute(alpha, beta);\n";
+	let mut fmt = Formatter::new();
+	fmt.add(line_span(0, 4, 9), Some("binding".to_string()), Style::Note);
+	fmt.add(line_span(0, 13, 33), Some("call".to_string()), Style::Error);
+	fmt.add(line_span(0, 21, 25), Some("first argument".to_string()), Style::Help);
+	fmt.add(line_span(0, 28, 31), Some("second argument".to_string()), Style::Help);
+	check("label_texts_do_not_overlap", &render(&fmt, source))
+}
+
+#[test]
+fn labels_sharing_a_column() {
+	let source = "let result = compute(alpha, beta);\n";
+	let mut fmt = Formatter::new();
+	fmt.add(line_span(0, 13, 33), Some("call".to_string()), Style::Error);
+	fmt.add(line_span(0, 21, 25), Some("first argument".to_string()), Style::Help);
+	fmt.add(line_span(0, 23, 25), Some("suffix".to_string()), Style::Note);
+	fmt.add(line_span(0, 25, 25), Some("last character".to_string()), Style::Warning);
+	check("labels_sharing_a_column", &render(&fmt, source))
+}
+
+#[test]
+fn formatting_challenge() {
+	let source = "################################\n".repeat(10);
+	let source = source.trim_end();
+	let mut fmt = Formatter::new();
+	fmt.add(Span::of_string(source, &DEFAULT_METRICS), Some("label Z".to_string()), Style::Error);
+	fmt.add(span((1, 8), (7, 8), (7, 9)), Some("label A\nwith\nmany\nlines\n...".to_string()), Style::Note);
+	fmt.add(span((1, 4), (7, 4), (7, 5)), Some("label B".to_string()), Style::Warning);
+	fmt.add(span((3, 0), (5, 4), (5, 5)), Some("label C".to_string()), Style::Help);
+	fmt.add(span((3, 0), (5, 4), (5, 5)), Some("label D".to_string()), Style::Error);
+	fmt.add(span((3, 5), (3, 12), (3, 13)), Some("label E".to_string()), Style::Note);
+	fmt.add(span((3, 25), (6, 12), (6, 13)), Some("label F".to_string()), Style::Warning);
+	fmt.add(span((8, 0), (8, 31), (9, 0)), Some("label G".to_string()), Style::Warning);
+	check("formatting_challenge", &render(&fmt, source))
+}
+
+#[test]
+fn empty_span() {
+	let source = "let x = 1\nlet y = 2;\n";
+	let mut fmt = Formatter::new();
+	fmt.add(
+		Position::new(0, 9).into(),
+		Some("expected `;`".to_string()),
+		Style::Error,
+	);
+	check("empty_span", &render(&fmt, source))
+}
+
+#[test]
+fn empty_span_between_characters() {
+	let source = "foo(a b)\n";
+	let mut fmt = Formatter::new();
+	fmt.add(
+		Position::new(0, 6).into(),
+		Some("expected `,`".to_string()),
+		Style::Error,
+	);
+	check("empty_span_between_characters", &render(&fmt, source))
+}
+
+#[test]
+fn end_of_file() {
+	let source = "fn main() {\n    foo(";
+	let mut fmt = Formatter::new();
+	fmt.add(
+		span((0, 10), (5, 0), (5, 1)),
+		Some("unclosed block".to_string()),
+		Style::Error,
+	);
+	fmt.add(
+		Position::new(3, 0).into(),
+		Some("unexpected end of file".to_string()),
+		Style::Error,
+	);
+	check("end_of_file", &render(&fmt, source))
+}
+
+#[test]
+fn tab() {
+	let source = "fn main() {\n\tlet x =\t42;\n}\n";
+	let mut fmt = Formatter::new();
+	fmt.add(line_span(1, 16, 17), Some("a number".to_string()), Style::Note);
+	fmt.add(line_span(1, 12, 12), Some("binding".to_string()), Style::Help);
+	check("tab", &render(&fmt, source))
+}
+
+#[test]
+fn tab_arrow() {
+	let source = "fn main() {\n\tlet x =\t42;\n}\n";
+	let mut fmt = Formatter::new();
+	fmt.set_tab_style(TabStyle::Arrow);
+	fmt.add(line_span(1, 16, 17), Some("a number".to_string()), Style::Note);
+	check("tab_arrow", &render(&fmt, source))
+}
+
+const INVISIBLE: &str = "let x\u{200b} = 1;\r\nlet y = \u{0}2;\n";
+
+#[test]
+fn invisible_characters() {
+	let metrics = VisibleMetrics::new(DEFAULT_METRICS);
+	let mut fmt = Formatter::new();
+	fmt.set_invisible_characters_visible(true);
+	fmt.add(line_span(0, 5, 12), Some("zero-width space".to_string()), Style::Error);
+	fmt.add(line_span(0, 18, 18), Some("carriage return".to_string()), Style::Warning);
+	fmt.add(line_span(1, 8, 8), Some("null character".to_string()), Style::Error);
+	check("invisible_characters", &render_with_metrics(&fmt, INVISIBLE, &metrics))
+}
+
+#[test]
+fn invisible_characters_default_metrics() {
+	let mut fmt = Formatter::new();
+	fmt.set_invisible_characters_visible(true);
+	fmt.add(line_span(0, 5, 5), Some("zero-width space".to_string()), Style::Error);
+	check("invisible_characters_default_metrics", &render(&fmt, INVISIBLE))
+}
+
+#[test]
+fn viewbox() {
+	let source: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+	let mut fmt = Formatter::new();
+	fmt.set_viewbox(Some(1));
+	fmt.add(line_span(2, 0, 3), Some("here".to_string()), Style::Error);
+	fmt.add(span((6, 5), (15, 5), (15, 6)), Some("there".to_string()), Style::Warning);
+	check("viewbox", &render(&fmt, &source))
+}
+
+#[test]
+fn no_viewbox() {
+	let source: String = (1..=8).map(|i| format!("line {}\n", i)).collect();
+	let mut fmt = Formatter::new();
+	fmt.set_viewbox(None);
+	fmt.add(line_span(1, 0, 3), Some("here".to_string()), Style::Error);
+	check("no_viewbox", &render(&fmt, &source))
+}
+
+#[test]
+fn multi_line_label() {
+	let mut fmt = Formatter::new();
+	fmt.add(
+		line_span(1, 4, 11),
+		Some("a macro\nwith a\nlong label".to_string()),
+		Style::Warning,
+	);
+	check("multi_line_label", &render(&fmt, HELLO))
+}
+
+#[test]
+fn short_mode() {
+	let mut fmt = Formatter::new();
+	fmt.set_mode(Mode::Short);
+	fmt.set_source_name(Some("main.rs".to_string()));
+	fmt.add(span((0, 10), (2, 0), (2, 1)), Some("a block".to_string()), Style::Error);
+	fmt.add(line_span(1, 13, 26), Some("a string".to_string()), Style::Note);
+	check("short_mode", &render(&fmt, HELLO))
+}
+
+#[test]
+fn compact_mode() {
+	let mut fmt = Formatter::new();
+	fmt.set_mode(Mode::Compact);
+	fmt.add(span((0, 10), (2, 0), (2, 1)), Some("a block".to_string()), Style::Error);
+	fmt.add(line_span(1, 13, 26), Some("a string".to_string()), Style::Note);
+	check("compact_mode", &render(&fmt, HELLO))
+}
+
+#[test]
+fn unicode_line_breaks() {
+	let source = "let x = 1;\r\nlet y = 2;\rlet z = 3;\u{2028}x + y + z\r\n";
+	let metrics = DefaultMetrics::with_line_breaks(LineBreaks::Unicode);
+	let mut fmt = Formatter::new();
+	fmt.add(span((0, 4), (2, 4), (2, 5)), Some("bindings".to_string()), Style::Note);
+	fmt.add(line_span(3, 4, 4), Some("use of `y`".to_string()), Style::Error);
+	check("unicode_line_breaks", &render_with_metrics(&fmt, source, &metrics))
+}